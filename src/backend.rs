@@ -0,0 +1,234 @@
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use arboard::{Clipboard, ImageData};
+use tokio::sync::mpsc::UnboundedReceiver;
+use crate::capture_hints::FormatReader;
+use crate::monitor::{self, Capture, CaptureControls, ClipboardContent};
+
+/// how long after our own write a matching clipboard change is still taken to be that write
+pub const SELF_WRITE_WINDOW: Duration = Duration::from_secs(2);
+
+/// entries we just put back on the clipboard, so the listener can tell our writes from everyone else's
+/// matched by content rather than by order, other apps can write in between and the os can report a write twice
+#[derive(Clone, Default)]
+pub struct ClipboardWriteSuppression {
+	writes: Arc<Mutex<Vec<SelfWrite>>>,
+}
+
+struct SelfWrite {
+	key: String,
+	id: i64,
+	at: Instant,
+	seen: bool,
+}
+
+/// what a clipboard change turned out to be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteOrigin {
+	/// someone else copied it
+	Other,
+	/// our write of entry `id`, reported for the first time
+	Ours(i64),
+	/// our write, reported again
+	Echo,
+}
+
+impl ClipboardWriteSuppression {
+	/// we're about to write entry `id` with `content`
+	pub fn expect(&self, id: i64, content: &ClipboardContent) {
+		if let Ok(mut writes) = self.writes.lock() {
+			writes.push(SelfWrite { key: write_key(content), id, at: Instant::now(), seen: false });
+		}
+	}
+
+	/// the write didn't happen after all
+	pub fn forget(&self, id: i64) {
+		if let Ok(mut writes) = self.writes.lock() {
+			writes.retain(|write| write.id != id);
+		}
+	}
+
+	/// whether a clipboard change is one of our own recent writes
+	pub fn origin(&self, content: &ClipboardContent) -> WriteOrigin {
+		let Ok(mut writes) = self.writes.lock() else {
+			return WriteOrigin::Other;
+		};
+		writes.retain(|write| write.at.elapsed() < SELF_WRITE_WINDOW);
+
+		let key = write_key(content);
+		let Some(write) = writes.iter_mut().rev().find(|write| write.key == key) else {
+			return WriteOrigin::Other;
+		};
+		if std::mem::replace(&mut write.seen, true) {
+			WriteOrigin::Echo
+		} else {
+			WriteOrigin::Ours(write.id)
+		}
+	}
+}
+
+/// what a write is recognised by when it comes back, the plain text or the decoded pixels
+/// markup and png encoding can change on the way through the clipboard
+fn write_key(content: &ClipboardContent) -> String {
+	match content {
+		ClipboardContent::Image(image) => match image::load_from_memory(&image.png) {
+			Ok(decoded) => {
+				let rgba = decoded.into_rgba8();
+				crate::db::hash_parts(&format!("image:{}x{}", rgba.width(), rgba.height()), rgba.as_raw())
+			}
+			Err(_) => crate::db::content_hash(content),
+		},
+		_ => crate::db::content_hash(&ClipboardContent::Text(content.plain_text().unwrap_or_default())),
+	}
+}
+
+/// the clipboard the app reads, writes and watches
+pub trait ClipboardBackend {
+	/// the richest representation currently on the clipboard
+	fn read(&self) -> anyhow::Result<ClipboardContent>;
+	/// replace the clipboard with `content`, in every format it was captured with
+	fn write(&self, content: &ClipboardContent) -> anyhow::Result<()>;
+	/// changes from now on, each call starts its own listener
+	fn subscribe(&self) -> UnboundedReceiver<Capture>;
+}
+
+pub type SharedBackend = Arc<dyn ClipboardBackend>;
+
+/// write entry `id` back to the clipboard, expecting it so it isn't recorded as a new copy
+pub fn write_suppressed(backend: &dyn ClipboardBackend, suppression: &ClipboardWriteSuppression, id: i64, content: &ClipboardContent) -> anyhow::Result<()> {
+	suppression.expect(id, content);
+	let result = backend.write(content);
+	if result.is_err() {
+		suppression.forget(id);
+	}
+	result
+}
+
+/// the os clipboard through arboard, watched by clipboard-master or data-control
+pub struct SystemClipboard {
+	controls: CaptureControls,
+}
+
+impl SystemClipboard {
+	/// `controls` decide what subscribers get to see
+	pub fn new(controls: CaptureControls) -> Self {
+		Self { controls }
+	}
+}
+
+impl ClipboardBackend for SystemClipboard {
+	fn read(&self) -> anyhow::Result<ClipboardContent> {
+		Ok(monitor::read_clipboard(&FormatReader::new()))
+	}
+
+	fn write(&self, content: &ClipboardContent) -> anyhow::Result<()> {
+		let mut clipboard = Clipboard::new()?;
+		match content {
+			ClipboardContent::Text(text) | ClipboardContent::Url(text) => clipboard.set_text(text.clone())?,
+			ClipboardContent::Html { html, text } => clipboard.set_html(html.clone(), Some(text.clone()))?,
+			ClipboardContent::Rtf { rtf, text } => set_rtf(&mut clipboard, rtf, text)?,
+			ClipboardContent::Files(paths) => clipboard.set().file_list(paths)?,
+			ClipboardContent::Image(image) => {
+				let rgba = image::load_from_memory(&image.png)?.into_rgba8();
+				let (width, height) = rgba.dimensions();
+				clipboard.set_image(ImageData {
+					width: width as usize,
+					height: height as usize,
+					bytes: Cow::Owned(rgba.into_raw()),
+				})?;
+			}
+			ClipboardContent::Empty => {}
+		}
+		Ok(())
+	}
+
+	fn subscribe(&self) -> UnboundedReceiver<Capture> {
+		monitor::start_listener(self.controls.clone())
+	}
+}
+
+/// put rich text on the clipboard along with its plain text, for apps that don't take rtf
+#[cfg(windows)]
+fn set_rtf(_clipboard: &mut Clipboard, rtf: &str, text: &str) -> anyhow::Result<()> {
+	use clipboard_win::{options, raw};
+
+	let format = clipboard_win::register_format(crate::capture_hints::RTF_FORMAT)
+		.ok_or_else(|| anyhow::anyhow!("couldn't register the rtf format"))?;
+	let _clipboard = clipboard_win::Clipboard::new_attempts(10)?;
+	raw::empty()?;
+	raw::set_string_with(text, options::NoClear)?;
+	raw::set_without_clear(format.get(), rtf.as_bytes())?;
+	Ok(())
+}
+
+/// on wayland both formats are offered through data-control, X11 only gets the plain text
+#[cfg(all(unix, not(target_os = "macos")))]
+fn set_rtf(clipboard: &mut Clipboard, rtf: &str, text: &str) -> anyhow::Result<()> {
+	use wl_clipboard_rs::copy::{MimeSource, MimeType, Options, Source};
+
+	if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+		let sources = vec![
+			MimeSource { source: Source::Bytes(rtf.as_bytes().into()), mime_type: MimeType::Specific(crate::capture_hints::RTF_FORMAT.to_string()) },
+			MimeSource { source: Source::Bytes(text.as_bytes().into()), mime_type: MimeType::Text },
+		];
+		match Options::new().copy_multi(sources) {
+			Ok(()) => return Ok(()),
+			Err(err) => eprintln!("Failed to offer rich text, pasting it as plain text: {err}"),
+		}
+	}
+	clipboard.set_text(text)?;
+	Ok(())
+}
+
+#[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+fn set_rtf(clipboard: &mut Clipboard, _rtf: &str, text: &str) -> anyhow::Result<()> {
+	clipboard.set_text(text)?;
+	Ok(())
+}
+
+/// clipboard that only lives in memory, for driving the capture pipeline without a display
+/// writes are reported to subscribers like the os would report them
+#[cfg(test)]
+pub struct MemoryClipboard {
+	content: Mutex<ClipboardContent>,
+	subscribers: Mutex<Vec<tokio::sync::mpsc::UnboundedSender<Capture>>>,
+}
+
+#[cfg(test)]
+impl MemoryClipboard {
+	pub fn new() -> Self {
+		Self { content: Mutex::new(ClipboardContent::Empty), subscribers: Mutex::new(Vec::new()) }
+	}
+
+	/// another app copied `content`
+	pub fn copy(&self, content: ClipboardContent) {
+		self.send(Capture { content, concealed: false, source: crate::monitor::CaptureSource::Clipboard, app: None });
+	}
+
+	/// a change as the listener would report it, for selections and concealed copies
+	pub fn send(&self, capture: Capture) {
+		if capture.source == crate::monitor::CaptureSource::Clipboard {
+			*self.content.lock().unwrap() = capture.content.clone();
+		}
+		self.subscribers.lock().unwrap().retain(|tx| tx.send(capture.clone()).is_ok());
+	}
+}
+
+#[cfg(test)]
+impl ClipboardBackend for MemoryClipboard {
+	fn read(&self) -> anyhow::Result<ClipboardContent> {
+		Ok(self.content.lock().unwrap().clone())
+	}
+
+	fn write(&self, content: &ClipboardContent) -> anyhow::Result<()> {
+		self.copy(content.clone());
+		Ok(())
+	}
+
+	fn subscribe(&self) -> UnboundedReceiver<Capture> {
+		let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+		self.subscribers.lock().unwrap().push(tx);
+		rx
+	}
+}
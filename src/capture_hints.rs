@@ -0,0 +1,210 @@
+/// set by KeePassXC and friends on linux, a value of `secret` asks managers not to record
+pub const KDE_PASSWORD_MANAGER_HINT: &str = "x-kde-passwordManagerHint";
+/// windows, presence alone means the copy shouldn't be processed at all
+pub const EXCLUDE_FROM_MONITOR: &str = "ExcludeClipboardContentFromMonitorProcessing";
+/// windows, a DWORD of 0 keeps the copy out of clipboard history
+pub const CAN_INCLUDE_IN_HISTORY: &str = "CanIncludeInClipboardHistory";
+/// nspasteboard.org, the copy is a password that shouldn't be shown
+pub const CONCEALED_TYPE: &str = "org.nspasteboard.ConcealedType";
+/// nspasteboard.org, the copy is short-lived and shouldn't be recorded
+pub const TRANSIENT_TYPE: &str = "org.nspasteboard.TransientType";
+
+/// name rich text is offered under
+#[cfg(windows)]
+pub const RTF_FORMAT: &str = "Rich Text Format";
+#[cfg(not(windows))]
+pub const RTF_FORMAT: &str = "text/rtf";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureDecision {
+	/// no markers, record as usual
+	Record,
+	/// the source asked not to be recorded
+	Ignore,
+	/// a password, recorded only as the sensitive policy allows
+	Conceal,
+}
+
+/// decide what to do with a copy from the markers password managers put on the clipboard
+/// `formats` are the offered MIME types or format names
+/// `read` fetches a format's value, for markers whose meaning depends on it
+pub fn capture_decision<S: AsRef<str>>(formats: &[S], mut read: impl FnMut(&str) -> Option<Vec<u8>>) -> CaptureDecision {
+	let offers = |name: &str| formats.iter().any(|f| f.as_ref() == name);
+
+	if offers(EXCLUDE_FROM_MONITOR) || offers(TRANSIENT_TYPE) {
+		return CaptureDecision::Ignore;
+	}
+	// an unreadable value is treated as the restrictive one, these are only ever set to opt out
+	if offers(CAN_INCLUDE_IN_HISTORY) && read(CAN_INCLUDE_IN_HISTORY).is_none_or(|value| value.iter().all(|&b| b == 0)) {
+		return CaptureDecision::Ignore;
+	}
+	if offers(KDE_PASSWORD_MANAGER_HINT) && read(KDE_PASSWORD_MANAGER_HINT).is_none_or(|value| value.trim_ascii() == b"secret") {
+		return CaptureDecision::Ignore;
+	}
+	if offers(CONCEALED_TYPE) {
+		return CaptureDecision::Conceal;
+	}
+	CaptureDecision::Record
+}
+
+/// lists and reads formats on the system clipboard by name
+#[cfg(windows)]
+#[derive(Default)]
+pub struct FormatReader;
+
+#[cfg(windows)]
+impl FormatReader {
+	pub fn new() -> Self {
+		Self
+	}
+
+	pub fn decision(&self) -> CaptureDecision {
+		use clipboard_win::raw;
+
+		let Ok(_clipboard) = clipboard_win::Clipboard::new_attempts(10) else {
+			return CaptureDecision::Record;
+		};
+		let formats: Vec<(String, u32)> = clipboard_win::EnumFormats::new()
+			.filter_map(|format| raw::format_name_big(format).map(|name| (name, format)))
+			.collect();
+		let names: Vec<&str> = formats.iter().map(|(name, _)| name.as_str()).collect();
+
+		capture_decision(&names, |name| {
+			let (_, format) = formats.iter().find(|(n, _)| n == name)?;
+			read_open(*format)
+		})
+	}
+
+	/// value of a registered format, `None` if it isn't on the clipboard
+	pub fn read(&self, name: &str) -> Option<Vec<u8>> {
+		let format = clipboard_win::register_format(name)?.get();
+		let _clipboard = clipboard_win::Clipboard::new_attempts(10).ok()?;
+		read_open(format)
+	}
+}
+
+/// read a format while the clipboard is already open
+#[cfg(windows)]
+fn read_open(format: u32) -> Option<Vec<u8>> {
+	if !clipboard_win::is_format_avail(format) {
+		return None;
+	}
+	let mut value = Vec::new();
+	clipboard_win::raw::get_vec(format, &mut value).ok()?;
+	Some(value)
+}
+
+/// lists and reads formats on the system clipboard, through the TARGETS of the X11 selection
+/// holds on to its connection, `None` when there's no X server to talk to
+#[cfg(all(unix, not(target_os = "macos")))]
+pub struct FormatReader {
+	x11: Option<x11_clipboard::Clipboard>,
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl Default for FormatReader {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl FormatReader {
+	const TIMEOUT: std::time::Duration = std::time::Duration::from_millis(200);
+
+	pub fn new() -> Self {
+		Self { x11: x11_clipboard::Clipboard::new().ok() }
+	}
+
+	pub fn decision(&self) -> CaptureDecision {
+		use x11rb::protocol::xproto::ConnectionExt;
+
+		let Some(clipboard) = &self.x11 else {
+			return CaptureDecision::Record;
+		};
+		let atoms = &clipboard.getter.atoms;
+		let Ok(targets) = clipboard.load(atoms.clipboard, atoms.targets, atoms.property, Self::TIMEOUT) else {
+			return CaptureDecision::Record;
+		};
+
+		let connection = &clipboard.getter.connection;
+		let names: Vec<String> = targets
+			.chunks_exact(4)
+			.map(|atom| u32::from_ne_bytes([atom[0], atom[1], atom[2], atom[3]]))
+			.filter_map(|atom| connection.get_atom_name(atom).ok()?.reply().ok())
+			.map(|reply| String::from_utf8_lossy(&reply.name).into_owned())
+			.collect();
+
+		capture_decision(&names, |name| self.read(name))
+	}
+
+	/// value of a target, `None` if the owner doesn't offer it
+	pub fn read(&self, name: &str) -> Option<Vec<u8>> {
+		let clipboard = self.x11.as_ref()?;
+		let atoms = &clipboard.getter.atoms;
+		let target = clipboard.getter.get_atom(name).ok()?;
+		let value = clipboard.load(atoms.clipboard, target, atoms.property, Self::TIMEOUT).ok()?;
+		(!value.is_empty()).then_some(value)
+	}
+}
+
+/// no format listing on other platforms, everything is recorded
+#[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+#[derive(Default)]
+pub struct FormatReader;
+
+#[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+impl FormatReader {
+	pub fn new() -> Self {
+		Self
+	}
+
+	pub fn decision(&self) -> CaptureDecision {
+		CaptureDecision::Record
+	}
+
+	pub fn read(&self, _name: &str) -> Option<Vec<u8>> {
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn no_values(_: &str) -> Option<Vec<u8>> {
+		None
+	}
+
+	#[test]
+	fn plain_copies_are_recorded() {
+		assert_eq!(capture_decision(&["UTF8_STRING", "text/plain", "TARGETS"], no_values), CaptureDecision::Record);
+		assert_eq!(capture_decision::<&str>(&[], no_values), CaptureDecision::Record);
+	}
+
+	#[test]
+	fn exclusion_formats_are_ignored() {
+		assert_eq!(capture_decision(&["CF_UNICODETEXT", EXCLUDE_FROM_MONITOR], no_values), CaptureDecision::Ignore);
+		assert_eq!(capture_decision(&["public.utf8-plain-text", TRANSIENT_TYPE], no_values), CaptureDecision::Ignore);
+	}
+
+	#[test]
+	fn history_opt_out_depends_on_its_value() {
+		let formats = ["CF_UNICODETEXT", CAN_INCLUDE_IN_HISTORY];
+		assert_eq!(capture_decision(&formats, |_| Some(vec![0, 0, 0, 0])), CaptureDecision::Ignore);
+		assert_eq!(capture_decision(&formats, |_| Some(vec![1, 0, 0, 0])), CaptureDecision::Record);
+		assert_eq!(capture_decision(&formats, no_values), CaptureDecision::Ignore);
+	}
+
+	#[test]
+	fn kde_hint_ignores_secrets() {
+		let formats = ["text/plain", KDE_PASSWORD_MANAGER_HINT];
+		assert_eq!(capture_decision(&formats, |_| Some(b"secret".to_vec())), CaptureDecision::Ignore);
+		assert_eq!(capture_decision(&formats, |_| Some(b"public".to_vec())), CaptureDecision::Record);
+	}
+
+	#[test]
+	fn concealed_copies_are_marked() {
+		assert_eq!(capture_decision(&["public.utf8-plain-text", CONCEALED_TYPE], no_values), CaptureDecision::Conceal);
+	}
+}
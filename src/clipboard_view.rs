@@ -1,8 +1,23 @@
 use chrono::Local;
 use dioxus::prelude::*;
 
-use crate::db::ClipboardEntry;
-use crate::monitor::ClipboardContent;
+use crate::db::{ClipboardEntry, SNIPPET_END, SNIPPET_START};
+use crate::monitor::{CaptureSource, ClipboardContent};
+
+/// asset handler name the main window serves images under, as `/{IMAGE_ASSET_HANDLER}/thumb/{id}` or `/full/{id}`
+pub const IMAGE_ASSET_HANDLER: &str = "clipboard-image";
+
+/// human readable byte size, like "245 KB"
+fn format_size(bytes: usize) -> String {
+	const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+	let mut size = bytes as f64;
+	let mut unit = 0;
+	while size >= 1024.0 && unit < UNITS.len() - 1 {
+		size /= 1024.0;
+		unit += 1;
+	}
+	if unit == 0 { format!("{bytes} B") } else { format!("{size:.1} {}", UNITS[unit]) }
+}
 
 /// highlight text match fragments
 fn highlight_fragments(text: &str, query: &str) -> Vec<(String, bool)> {
@@ -29,6 +44,22 @@ fn highlight_fragments(text: &str, query: &str) -> Vec<(String, bool)> {
 	result
 }
 
+/// split a full-text search snippet into fragments on its match markers
+fn snippet_fragments(snippet: &str) -> Vec<(String, bool)> {
+	let mut result = Vec::new();
+	for (i, part) in snippet.split(SNIPPET_START).enumerate() {
+		if i == 0 {
+			result.push((part.to_string(), false));
+			continue;
+		}
+		let (matched, rest) = part.split_once(SNIPPET_END).unwrap_or((part, ""));
+		result.push((matched.to_string(), true));
+		result.push((rest.to_string(), false));
+	}
+	result.retain(|(frag, _)| !frag.is_empty());
+	result
+}
+
 /// display a single clipboard entry
 #[component]
 pub fn ClipboardView(
@@ -37,14 +68,38 @@ pub fn ClipboardView(
 	on_context_menu: EventHandler<(i64, f64, f64)>,
 	search_query: String,
 	similarity: f32,
+	/// keyword search excerpt shown instead of the start of the text
+	snippet: Option<String>,
 ) -> Element {
 	let time_str = entry.copied_at.with_timezone(&Local).format("%b %d %Y, %I:%M %p").to_string(); // jan 1, 2021, 12:00 PM
 	let entry_id = entry.id;
-	let header = if search_query.is_empty() {
+	let mut header = if search_query.is_empty() {
 		time_str.clone()
 	} else {
 		format!("{time_str} — sim: {similarity:.3}")
 	};
+	if entry.use_count > 1 {
+		header.push_str(&format!(" · copied {}×", entry.use_count));
+	}
+	match entry.content {
+		ClipboardContent::Html { .. } => header.push_str(" · html"),
+		ClipboardContent::Rtf { .. } => header.push_str(" · rtf"),
+		_ => {}
+	}
+	// the window title is too long for the header, it shows on hover
+	let window_title = entry.app.as_ref().map(|app| app.title.clone()).unwrap_or_default();
+	if let Some(ref app) = entry.app {
+		header.push_str(&format!(" · {}", app.name));
+	}
+	if entry.source == CaptureSource::Primary {
+		header.push_str(" · selection");
+	}
+	if let Some(expires_at) = entry.expires_at {
+		header.push_str(&format!(" · expires {}", expires_at.format("%I:%M %p")));
+	}
+	if entry.pinned {
+		header.insert_str(0, "📌 ");
+	}
 	rsx! {
 		div { class: "flex items-start gap-3 p-3 rounded-lg border border-slate-800 bg-slate-900/50 hover:bg-slate-800 hover:border-slate-700 transition-all group relative",
 			oncontextmenu: move |evt| {
@@ -53,10 +108,14 @@ pub fn ClipboardView(
 				on_context_menu.call((entry_id, coords.x, coords.y));
 			},
 			div { class: "flex-1 flex flex-col gap-1.5 min-w-0",
-				span { class: "text-[10px] uppercase font-semibold tracking-wider text-slate-500", "{header}" }
+				span { class: "text-[10px] uppercase font-semibold tracking-wider text-slate-500 truncate", title: "{window_title}", "{header}" }
 				match entry.content {
-					ClipboardContent::Text(ref text) => {
-						let fragments = highlight_fragments(text, &search_query);
+					// formatted text is listed by its plain text, the markup comes back when it's pasted
+					ClipboardContent::Text(ref text) | ClipboardContent::Html { ref text, .. } | ClipboardContent::Rtf { ref text, .. } => {
+						let fragments = match snippet {
+							Some(ref snippet) => snippet_fragments(snippet),
+							None => highlight_fragments(text, &search_query),
+						};
 						rsx! {
 							p { class: "text-sm text-slate-300 line-clamp-4 font-mono break-all leading-relaxed",
 								for (i, (frag, is_match)) in fragments.iter().enumerate() {
@@ -73,9 +132,26 @@ pub fn ClipboardView(
 							}
 						}
 					},
-					ClipboardContent::Image(ref src) => rsx! {
-						div { class: "rounded-md overflow-hidden bg-slate-950 border border-slate-800",
-							img { src: "{src}", class: "max-w-full max-h-64 h-auto object-contain" }
+					ClipboardContent::Url(ref url) => rsx! {
+						p { class: "text-sm text-sky-400 underline underline-offset-2 truncate font-mono", title: "{url}", "{url}" }
+					},
+					ClipboardContent::Files(ref paths) => rsx! {
+						ul { class: "flex flex-col gap-0.5 text-sm text-slate-300",
+							for (i, path) in paths.iter().enumerate() {
+								li { key: "{i}", class: "truncate", title: "{path.display()}",
+									"📄 "
+									{path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|| path.display().to_string())}
+								}
+							}
+						}
+					},
+					ClipboardContent::Image(ref image) => {
+						let size = format_size(image.byte_size);
+						rsx! {
+							div { class: "rounded-md overflow-hidden bg-slate-950 border border-slate-800",
+								img { src: "/{IMAGE_ASSET_HANDLER}/thumb/{entry_id}", loading: "lazy", class: "max-w-full max-h-64 h-auto object-contain" }
+							}
+							span { class: "text-[10px] text-slate-500", "{image.width} × {image.height} · {size}" }
 						}
 					},
 					ClipboardContent::Empty => rsx! {
@@ -1,6 +1,15 @@
 use chrono::{DateTime, Local};
-use rusqlite::{Connection, params};
-use crate::monitor::ClipboardContent;
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use rusqlite::types::Value;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use crate::migrations;
+use crate::retention::{self, RetentionPolicy};
+use crate::monitor::{CaptureSource, ClipboardContent, ClipboardImage};
+use crate::source_app::SourceApp;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ClipboardEntry {
@@ -8,6 +17,46 @@ pub struct ClipboardEntry {
 	pub content: ClipboardContent,
 	pub copied_at: DateTime<Local>,
 	pub embedding: Option<Vec<f32>>,
+	/// how many times this content has been copied
+	pub use_count: i64,
+	/// pinned entries are listed first and never pruned
+	pub pinned: bool,
+	/// flagged sensitive entries are deleted at this time
+	pub expires_at: Option<DateTime<Local>>,
+	pub source: CaptureSource,
+	/// the application it was copied in, when that could be found out
+	pub app: Option<SourceApp>,
+}
+
+impl ClipboardEntry {
+	/// where the next history page starts after this entry
+	pub fn cursor(&self) -> PageCursor {
+		PageCursor { copied_at: self.copied_at, id: self.id }
+	}
+
+	/// drop the image payload, rich markup and embedding, matching the entries `load_page` returns
+	pub fn into_page_entry(mut self) -> Self {
+		match self.content {
+			ClipboardContent::Image(ref mut image) => image.png = Vec::new(),
+			ClipboardContent::Html { ref mut html, .. } => html.clear(),
+			ClipboardContent::Rtf { ref mut rtf, .. } => rtf.clear(),
+			_ => {}
+		}
+		self.embedding = None;
+		self
+	}
+}
+
+pub type SharedDatabase = Arc<Mutex<Database>>;
+
+/// number of entries fetched per history page
+pub const PAGE_SIZE: usize = 50;
+
+/// position in the history, pages are ordered newest first by (copied_at, id)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PageCursor {
+	pub copied_at: DateTime<Local>,
+	pub id: i64,
 }
 
 fn embedding_to_bytes(embedding: &[f32]) -> Vec<u8> {
@@ -18,89 +67,778 @@ fn bytes_to_embedding(bytes: &[u8]) -> Vec<f32> {
 	bytes.chunks_exact(4).map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]])).collect()
 }
 
+/// sha-256 hex digest over the stored content type and payload
+pub fn hash_parts(content_type: &str, payload: &[u8]) -> String {
+	let mut hasher = Sha256::new();
+	hasher.update(content_type.as_bytes());
+	hasher.update([0]);
+	hasher.update(payload);
+	hasher.finalize().iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// split content into the (content_type, content, rich_content) stored in the table, image bytes live in `image_data`
+/// `content` is the plain text, file lists are stored one path per line
+fn content_columns(content: &ClipboardContent) -> (&'static str, Cow<'_, str>, Option<&str>) {
+	match content {
+		ClipboardContent::Text(t) => ("text", Cow::Borrowed(t), None),
+		ClipboardContent::Image(_) => ("image", Cow::Borrowed(""), None),
+		ClipboardContent::Html { html, text } => ("html", Cow::Borrowed(text), Some(html)),
+		ClipboardContent::Rtf { rtf, text } => ("rtf", Cow::Borrowed(text), Some(rtf)),
+		ClipboardContent::Files(_) => ("files", Cow::Owned(content.plain_text().unwrap_or_default()), None),
+		ClipboardContent::Url(url) => ("url", Cow::Borrowed(url), None),
+		ClipboardContent::Empty => ("empty", Cow::Borrowed(""), None),
+	}
+}
+
+/// hash used to find identical entries across sessions
+pub fn content_hash(content: &ClipboardContent) -> String {
+	match content {
+		ClipboardContent::Image(image) => hash_parts("image", &image.png),
+		// a link is the same copy as its text, selections and transforms only ever produce text
+		ClipboardContent::Url(url) => hash_parts("text", url.as_bytes()),
+		ClipboardContent::Html { html, .. } => hash_parts("html", html.as_bytes()),
+		ClipboardContent::Rtf { rtf, .. } => hash_parts("rtf", rtf.as_bytes()),
+		_ => {
+			let (content_type, content, _) = content_columns(content);
+			hash_parts(content_type, content.as_bytes())
+		}
+	}
+}
+
+/// marks the start of a matched term in `TextMatch::snippet`
+pub const SNIPPET_START: char = '\u{2}';
+/// marks the end of a matched term in `TextMatch::snippet`
+pub const SNIPPET_END: char = '\u{3}';
+
+/// a full-text search hit
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextMatch {
+	pub id: i64,
+	/// bm25 score, lower is a better match
+	pub rank: f64,
+	/// excerpt around the match, terms wrapped in `SNIPPET_START`/`SNIPPET_END`
+	pub snippet: String,
+}
+
+/// split `app:name` filters out of a search, returns the app filter and the rest of the query
+/// the last filter wins if there are several
+pub fn split_app_filter(query: &str) -> (Option<String>, String) {
+	let mut app = None;
+	let mut rest = Vec::new();
+	for term in query.split_whitespace() {
+		match term.strip_prefix("app:") {
+			Some(name) if !name.is_empty() => app = Some(name.to_string()),
+			_ => rest.push(term),
+		}
+	}
+	(app, rest.join(" "))
+}
+
+/// turn user input into an fts5 query, bare words match as prefixes and "quoted text" as phrases
+fn fts_query(input: &str) -> Option<String> {
+	let mut terms = Vec::new();
+	for (i, part) in input.split('"').enumerate() {
+		let in_quotes = i % 2 == 1;
+		if in_quotes {
+			if !part.trim().is_empty() {
+				terms.push(format!("\"{}\"", part.trim()));
+			}
+		} else {
+			terms.extend(part.split_whitespace().map(|word| format!("\"{word}\"*")));
+		}
+	}
+
+	if terms.is_empty() { None } else { Some(terms.join(" ")) }
+}
+
+const ENTRY_COLUMNS: &str = "id, content_type, content, copied_at, embedding, use_count, image_data, image_width, image_height, image_size, pinned, expires_at, source, rich_content, source_app, window_title";
+
+/// like `ENTRY_COLUMNS` but without image payloads, rich markup or embeddings, for list rendering
+const PAGE_COLUMNS: &str = "id, content_type, content, copied_at, NULL, use_count, NULL, image_width, image_height, image_size, pinned, expires_at, source, NULL, source_app, window_title";
+
+/// map a row selected with `ENTRY_COLUMNS` or `PAGE_COLUMNS`
+fn row_to_entry(row: &Row) -> rusqlite::Result<ClipboardEntry> {
+	let id: i64 = row.get(0)?;
+	let content_type: String = row.get(1)?;
+	let content_str: String = row.get(2)?;
+	let copied_at_str: String = row.get(3)?;
+	let emb_bytes: Option<Vec<u8>> = row.get(4)?;
+	let use_count: i64 = row.get(5)?;
+	let pinned: bool = row.get(10)?;
+	let expires_at: Option<String> = row.get(11)?;
+	let source = CaptureSource::parse(&row.get::<_, String>(12)?);
+	let rich_content: Option<String> = row.get(13)?;
+	let app_name: Option<String> = row.get(14)?;
+	let window_title: Option<String> = row.get(15)?;
+	let app = app_name.map(|name| SourceApp { name, title: window_title.unwrap_or_default(), class: String::new() });
+
+	let content = match content_type.as_str() {
+		"text" => ClipboardContent::Text(content_str),
+		"image" => ClipboardContent::Image(ClipboardImage {
+			png: row.get::<_, Option<Vec<u8>>>(6)?.unwrap_or_default(),
+			width: row.get::<_, Option<u32>>(7)?.unwrap_or(0),
+			height: row.get::<_, Option<u32>>(8)?.unwrap_or(0),
+			byte_size: row.get::<_, Option<i64>>(9)?.unwrap_or(0) as usize,
+		}),
+		"html" => ClipboardContent::Html { html: rich_content.unwrap_or_default(), text: content_str },
+		"rtf" => ClipboardContent::Rtf { rtf: rich_content.unwrap_or_default(), text: content_str },
+		"files" => ClipboardContent::Files(content_str.lines().map(PathBuf::from).collect()),
+		"url" => ClipboardContent::Url(content_str),
+		_ => ClipboardContent::Empty,
+	};
+
+	let copied_at = DateTime::parse_from_rfc3339(&copied_at_str)
+		.map(|dt| dt.with_timezone(&Local))
+		.unwrap_or_else(|_| Local::now());
+
+	let expires_at = expires_at
+		.and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+		.map(|dt| dt.with_timezone(&Local));
+
+	let embedding = emb_bytes.filter(|b| !b.is_empty()).map(|b| bytes_to_embedding(&b));
+
+	Ok(ClipboardEntry { id, content, copied_at, embedding, use_count, pinned, expires_at, source, app })
+}
+
+/// per-user directory the database lives in
+/// `%LOCALAPPDATA%\shadowpaste` on windows, `$XDG_DATA_HOME/shadowpaste` (`~/.local/share/shadowpaste` by default) on linux
+pub fn data_dir() -> PathBuf {
+	dirs::data_local_dir()
+		.unwrap_or_else(|| PathBuf::from("."))
+		.join("shadowpaste")
+}
+
 pub struct Database {
 	conn: Connection
 }
 
 impl Database {
-	pub fn open() -> rusqlite::Result<Self> {
-		let db_path = dirs::data_local_dir()
-			.unwrap_or_else(|| std::path::PathBuf::from("."))
-			.join("shadowpaste")
-			.join("shadowpaste.db");
+	pub fn open() -> anyhow::Result<Self> {
+		let db_path = data_dir().join("shadowpaste.db");
 
 		// make sure the directory exists
 		if let Some(parent) = db_path.parent() {
 			std::fs::create_dir_all(parent).ok();
 		}
 
-		let conn = Connection::open(&db_path)?;
-
-		conn.execute_batch(
-			"CREATE TABLE IF NOT EXISTS clipboard_history (
-				id           INTEGER PRIMARY KEY AUTOINCREMENT,
-				content_type TEXT    NOT NULL,
-				content      TEXT    NOT NULL,
-				copied_at    TEXT    NOT NULL,
-				embedding    BLOB    NOT NULL
-			);"
-		)?;
+		Self::from_connection(Connection::open(&db_path)?)
+	}
 
+	/// wrap an open connection, migrating its schema to the latest version first
+	pub fn from_connection(mut conn: Connection) -> anyhow::Result<Self> {
+		migrations::migrate(&mut conn)?;
 		Ok(Self { conn })
 	}
 
+	/// insert a new entry, or bump the existing row if the same content is already stored
 	pub fn insert(&self, entry: &ClipboardEntry) -> rusqlite::Result<i64> {
-		let (content_type, content) = match &entry.content {
-			ClipboardContent::Text(t) => ("text", t.clone()),
-			ClipboardContent::Image(s) => ("image", s.clone()),
-			ClipboardContent::Empty => ("empty", String::new()),
+		let (content_type, content, rich_content) = content_columns(&entry.content);
+		let hash = content_hash(&entry.content);
+
+		// the column is NOT NULL, entries without an embedding (flagged, or copied before the model loaded) get an empty blob
+		let emb_bytes = entry.embedding.as_deref().map(embedding_to_bytes).unwrap_or_default();
+
+		let image = match &entry.content {
+			ClipboardContent::Image(image) => Some(image),
+			_ => None,
 		};
+		let thumbnail = image.and_then(|image| image.thumbnail());
 
-		let emb_bytes: Option<Vec<u8>> = entry.embedding.as_ref().map(|e| embedding_to_bytes(e));
+		let tx = self.conn.unchecked_transaction()?;
+		let id = tx.query_row(
+			"INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count,
+					image_data, image_width, image_height, image_size, thumbnail, expires_at, source, rich_content,
+					source_app, window_title)
+				VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+				ON CONFLICT(content_hash) DO UPDATE SET
+					copied_at = excluded.copied_at, use_count = use_count + 1, expires_at = excluded.expires_at,
+					source = CASE WHEN excluded.source = 'clipboard' THEN 'clipboard' ELSE source END,
+					source_app = COALESCE(excluded.source_app, source_app),
+					window_title = CASE WHEN excluded.source_app IS NULL THEN window_title ELSE excluded.window_title END
+				RETURNING id",
+			params![
+				content_type, content, entry.copied_at.to_rfc3339(), emb_bytes, hash, entry.use_count,
+				image.map(|image| &image.png), image.map(|image| image.width), image.map(|image| image.height),
+				image.map(|image| image.png.len() as i64), thumbnail, entry.expires_at.map(|at| at.to_rfc3339()),
+				entry.source.as_str(), rich_content,
+				entry.app.as_ref().map(|app| &app.name), entry.app.as_ref().map(|app| &app.title),
+			],
+			|row| row.get(0),
+		)?;
 
-		self.conn.execute(
-			"INSERT INTO clipboard_history (content_type, content, copied_at, embedding) VALUES (?1, ?2, ?3, ?4)",
-			params![content_type, content, entry.copied_at.to_rfc3339(), emb_bytes],
+		// keep the keyword index in sync, everything but images is searchable by its plain text
+		if let Some(text) = entry.content.plain_text() {
+			tx.execute("DELETE FROM clipboard_fts WHERE rowid = ?1", params![id])?;
+			tx.execute("INSERT INTO clipboard_fts (rowid, content) VALUES (?1, ?2)", params![id, text])?;
+		}
+
+		tx.commit()?;
+		Ok(id)
+	}
+
+	/// if this content was copied before, move it to `copied_at`, bump its use count and replace its expiry
+	/// copying a former selection through the clipboard makes it a clipboard entry
+	/// the entry takes on `app` as where it was last copied from, if known
+	/// returns the updated entry so its stored embedding can be reused
+	pub fn touch_existing(
+		&self,
+		content: &ClipboardContent,
+		source: CaptureSource,
+		app: Option<&SourceApp>,
+		copied_at: DateTime<Local>,
+		expires_at: Option<DateTime<Local>>,
+	) -> rusqlite::Result<Option<ClipboardEntry>> {
+		let hash = content_hash(content);
+
+		let updated = self.conn.execute(
+			"UPDATE clipboard_history SET copied_at = ?1, use_count = use_count + 1, expires_at = ?2,
+					source = CASE WHEN ?3 = 'clipboard' THEN 'clipboard' ELSE source END,
+					source_app = COALESCE(?5, source_app),
+					window_title = CASE WHEN ?5 IS NULL THEN window_title ELSE ?6 END
+				WHERE content_hash = ?4",
+			params![
+				copied_at.to_rfc3339(), expires_at.map(|at| at.to_rfc3339()), source.as_str(), hash,
+				app.map(|app| &app.name), app.map(|app| &app.title),
+			],
 		)?;
+		if updated == 0 {
+			return Ok(None);
+		}
+
+		self.conn.query_row(
+			&format!("SELECT {ENTRY_COLUMNS} FROM clipboard_history WHERE content_hash = ?1"),
+			params![hash],
+			row_to_entry,
+		).optional()
+	}
 
-		Ok(self.conn.last_insert_rowid())
+	/// move an entry pasted back from history to `copied_at` and bump its use count, `None` if it's gone
+	pub fn bump(&self, id: i64, copied_at: DateTime<Local>) -> rusqlite::Result<Option<ClipboardEntry>> {
+		self.conn.query_row(
+			&format!("UPDATE clipboard_history SET copied_at = ?1, use_count = use_count + 1 WHERE id = ?2 RETURNING {ENTRY_COLUMNS}"),
+			params![copied_at.to_rfc3339(), id],
+			row_to_entry,
+		).optional()
 	}
 
 	pub fn delete_by_id(&self, id: i64) -> rusqlite::Result<()> {
-		self.conn.execute(
+		let tx = self.conn.unchecked_transaction()?;
+		tx.execute(
 			"DELETE FROM clipboard_history WHERE id = ?1",
 			params![id],
 		)?;
-		Ok(())
+		tx.execute("DELETE FROM clipboard_fts WHERE rowid = ?1", params![id])?;
+		tx.commit()
+	}
+
+	/// delete a selection that was dragged out further, unless it was pinned or copied through the clipboard since
+	/// returns whether it was deleted
+	pub fn delete_replaced_selection(&self, id: i64) -> rusqlite::Result<bool> {
+		let tx = self.conn.unchecked_transaction()?;
+		let deleted = tx.execute(
+			"DELETE FROM clipboard_history WHERE id = ?1 AND source = 'primary' AND pinned = 0",
+			params![id],
+		)?;
+		if deleted > 0 {
+			tx.execute("DELETE FROM clipboard_fts WHERE rowid = ?1", params![id])?;
+		}
+		tx.commit()?;
+		Ok(deleted > 0)
 	}
 
-	pub fn load_all(&self) -> rusqlite::Result<Vec<ClipboardEntry>> {
+	/// delete flagged entries whose expiry has passed, pinning one keeps it, returns the removed ids
+	pub fn delete_expired(&self, now: DateTime<Local>) -> rusqlite::Result<Vec<i64>> {
+		let tx = self.conn.unchecked_transaction()?;
+		let ids = {
+			let mut statement = tx.prepare(
+				"DELETE FROM clipboard_history WHERE expires_at IS NOT NULL AND expires_at <= ?1 AND pinned = 0 RETURNING id",
+			)?;
+			let ids = statement.query_map(params![now.to_rfc3339()], |row| row.get::<_, i64>(0))?;
+			ids.collect::<rusqlite::Result<Vec<_>>>()?
+		};
+		for id in &ids {
+			tx.execute("DELETE FROM clipboard_fts WHERE rowid = ?1", params![id])?;
+		}
+		tx.commit()?;
+		Ok(ids)
+	}
+
+	/// delete every entry that isn't pinned, returns the removed ids
+	pub fn clear_history(&self) -> rusqlite::Result<Vec<i64>> {
+		let tx = self.conn.unchecked_transaction()?;
+		let ids = {
+			let mut statement = tx.prepare("DELETE FROM clipboard_history WHERE pinned = 0 RETURNING id")?;
+			let ids = statement.query_map([], |row| row.get::<_, i64>(0))?;
+			ids.collect::<rusqlite::Result<Vec<_>>>()?
+		};
+		for id in &ids {
+			tx.execute("DELETE FROM clipboard_fts WHERE rowid = ?1", params![id])?;
+		}
+		tx.commit()?;
+		Ok(ids)
+	}
+
+	/// delete entries that fall outside the retention policy, returns the removed ids
+	/// pinned entries are never removed and don't count towards any limit
+	pub fn prune(&self, policy: &RetentionPolicy, now: DateTime<Local>) -> rusqlite::Result<Vec<i64>> {
+		const ENTRY_SIZE: &str = "length(content) + IFNULL(length(embedding), 0) + IFNULL(length(image_data), 0) + IFNULL(length(thumbnail), 0) + IFNULL(length(rich_content), 0)";
+
+		let mut doomed = BTreeSet::new();
+		let mut collect = |sql: &str, param: Value| -> rusqlite::Result<()> {
+			let mut statement = self.conn.prepare(sql)?;
+			for id in statement.query_map([param], |row| row.get::<_, i64>(0))? {
+				doomed.insert(id?);
+			}
+			Ok(())
+		};
+
+		if let Some(cutoff) = retention::age_cutoff(now, policy.max_age_days) {
+			collect("SELECT id FROM clipboard_history WHERE pinned = 0 AND copied_at < ?1", cutoff.into())?;
+		}
+		if let Some(cutoff) = retention::age_cutoff(now, policy.max_image_age_days) {
+			collect("SELECT id FROM clipboard_history WHERE pinned = 0 AND content_type = 'image' AND copied_at < ?1", cutoff.into())?;
+		}
+		if let Some(max) = policy.max_entries {
+			collect("SELECT id FROM clipboard_history WHERE pinned = 0 ORDER BY copied_at DESC, id DESC LIMIT -1 OFFSET ?1", (max as i64).into())?;
+		}
+		if let Some(max) = policy.max_images {
+			collect(
+				"SELECT id FROM clipboard_history WHERE pinned = 0 AND content_type = 'image' ORDER BY copied_at DESC, id DESC LIMIT -1 OFFSET ?1",
+				(max as i64).into(),
+			)?;
+		}
+		// keep the newest entries that fit, everything after the running total crosses the cap goes
+		if let Some(max_bytes) = retention::megabytes(policy.max_size_mb) {
+			collect(
+				&format!("SELECT id FROM (
+					SELECT id, SUM({ENTRY_SIZE}) OVER (ORDER BY copied_at DESC, id DESC) AS running
+						FROM clipboard_history WHERE pinned = 0
+				) WHERE running > ?1"),
+				max_bytes.into(),
+			)?;
+		}
+		if let Some(max_bytes) = retention::megabytes(policy.max_image_size_mb) {
+			collect(
+				&format!("SELECT id FROM (
+					SELECT id, SUM({ENTRY_SIZE}) OVER (ORDER BY copied_at DESC, id DESC) AS running
+						FROM clipboard_history WHERE pinned = 0 AND content_type = 'image'
+				) WHERE running > ?1"),
+				max_bytes.into(),
+			)?;
+		}
+
+		let tx = self.conn.unchecked_transaction()?;
+		for id in &doomed {
+			tx.execute("DELETE FROM clipboard_history WHERE id = ?1", params![id])?;
+			tx.execute("DELETE FROM clipboard_fts WHERE rowid = ?1", params![id])?;
+		}
+		tx.commit()?;
+
+		Ok(doomed.into_iter().collect())
+	}
+
+	/// reclaim the space left by deleted rows once enough of the file is free pages
+	pub fn vacuum_if_needed(&self) -> rusqlite::Result<bool> {
+		let page_count: i64 = self.conn.pragma_query_value(None, "page_count", |row| row.get(0))?;
+		let free_pages: i64 = self.conn.pragma_query_value(None, "freelist_count", |row| row.get(0))?;
+		if page_count == 0 || (free_pages as f64) / (page_count as f64) < retention::VACUUM_FREE_RATIO {
+			return Ok(false);
+		}
+		self.conn.execute_batch("VACUUM")?;
+		Ok(true)
+	}
+
+	/// keyword search over text entries, best bm25 matches first
+	pub fn search_text(&self, query: &str, limit: usize) -> rusqlite::Result<Vec<TextMatch>> {
+		let Some(fts_query) = fts_query(query) else {
+			return Ok(Vec::new());
+		};
+
 		let mut statement = self.conn.prepare(
-			"SELECT id, content_type, content, copied_at, embedding FROM clipboard_history ORDER BY copied_at ASC"
+			"SELECT rowid, bm25(clipboard_fts), snippet(clipboard_fts, 0, ?2, ?3, '…', 16)
+				FROM clipboard_fts WHERE clipboard_fts MATCH ?1 ORDER BY rank LIMIT ?4"
 		)?;
 
-		let entries = statement.query_map([], |row| {
-			let id: i64 = row.get(0)?;
-			let content_type: String = row.get(1)?;
-			let content_str: String = row.get(2)?;
-			let copied_at_str: String = row.get(3)?;
-			let emb_bytes: Option<Vec<u8>> = row.get(4)?;
+		let matches = statement.query_map(
+			params![fts_query, SNIPPET_START.to_string(), SNIPPET_END.to_string(), limit as i64],
+			|row| Ok(TextMatch { id: row.get(0)?, rank: row.get(1)?, snippet: row.get(2)? }),
+		)?.collect::<Result<Vec<_>, _>>()?;
+
+		Ok(matches)
+	}
+
+	/// page-style entries copied in an app whose name contains `app`, case-insensitively, newest first
+	pub fn load_from_app(&self, app: &str, limit: usize) -> rusqlite::Result<Vec<ClipboardEntry>> {
+		let mut statement = self.conn.prepare(&format!(
+			"SELECT {PAGE_COLUMNS} FROM clipboard_history
+				WHERE instr(lower(source_app), lower(?1)) > 0
+				ORDER BY copied_at DESC, id DESC LIMIT ?2"
+		))?;
+		let entries = statement.query_map(params![app, limit as i64], row_to_entry)?.collect::<Result<Vec<_>, _>>()?;
+		Ok(entries)
+	}
+
+	/// patterns of apps whose copies aren't recorded, see `SourceApp::matches`
+	pub fn load_ignored_apps(&self) -> rusqlite::Result<Vec<String>> {
+		let mut statement = self.conn.prepare("SELECT pattern FROM ignored_apps ORDER BY pattern")?;
+		let patterns = statement.query_map([], |row| row.get(0))?.collect::<Result<Vec<_>, _>>()?;
+		Ok(patterns)
+	}
 
-			let content = match content_type.as_str() {
-				"text" => ClipboardContent::Text(content_str),
-				"image" => ClipboardContent::Image(content_str),
-				_ => ClipboardContent::Empty,
-			};
+	pub fn add_ignored_app(&self, pattern: &str) -> rusqlite::Result<()> {
+		self.conn.execute("INSERT OR IGNORE INTO ignored_apps (pattern) VALUES (?1)", params![pattern])?;
+		Ok(())
+	}
 
-			let copied_at = DateTime::parse_from_rfc3339(&copied_at_str)
-				.map(|dt| dt.with_timezone(&Local))
-				.unwrap_or_else(|_| Local::now());
+	pub fn remove_ignored_app(&self, pattern: &str) -> rusqlite::Result<()> {
+		self.conn.execute("DELETE FROM ignored_apps WHERE pattern = ?1", params![pattern])?;
+		Ok(())
+	}
+
+	/// a page of unpinned history, newest first, starting after `after` (or at the newest entry)
+	/// image payloads are left empty, fetch them with `load_entry` when needed
+	/// leaves out PRIMARY selections unless `include_primary`
+	pub fn load_page(&self, after: Option<PageCursor>, limit: usize, include_primary: bool) -> rusqlite::Result<Vec<ClipboardEntry>> {
+		let (copied_at, id) = match after {
+			Some(cursor) => (Some(cursor.copied_at.to_rfc3339()), cursor.id),
+			None => (None, 0),
+		};
 
-			let embedding = emb_bytes.map(|b| bytes_to_embedding(&b));
+		let mut statement = self.conn.prepare(&format!(
+			"SELECT {PAGE_COLUMNS} FROM clipboard_history
+				WHERE pinned = 0 AND (?4 OR source != 'primary') AND (?1 IS NULL OR (copied_at, id) < (?1, ?2))
+				ORDER BY copied_at DESC, id DESC LIMIT ?3"
+		))?;
+
+		let entries = statement.query_map(params![copied_at, id, limit as i64, include_primary], row_to_entry)?.collect::<Result<Vec<_>, _>>()?;
+
+		Ok(entries)
+	}
+
+	/// every pinned entry, page-style and newest first
+	pub fn load_pinned(&self) -> rusqlite::Result<Vec<ClipboardEntry>> {
+		let mut statement = self.conn.prepare(&format!(
+			"SELECT {PAGE_COLUMNS} FROM clipboard_history WHERE pinned = 1 ORDER BY copied_at DESC, id DESC"
+		))?;
+		let entries = statement.query_map([], row_to_entry)?.collect::<Result<Vec<_>, _>>()?;
+		Ok(entries)
+	}
 
-			Ok(ClipboardEntry { id, content, copied_at, embedding })
-		})?.collect::<Result<Vec<_>, _>>()?;
+	pub fn set_pinned(&self, id: i64, pinned: bool) -> rusqlite::Result<()> {
+		self.conn.execute(
+			"UPDATE clipboard_history SET pinned = ?1 WHERE id = ?2",
+			params![pinned, id],
+		)?;
+		Ok(())
+	}
 
+	/// page-style entries (no image payloads or embeddings) for the given ids, in no particular order
+	pub fn load_by_ids(&self, ids: &[i64]) -> rusqlite::Result<Vec<ClipboardEntry>> {
+		let mut statement = self.conn.prepare(&format!("SELECT {PAGE_COLUMNS} FROM clipboard_history WHERE id = ?1"))?;
+		let mut entries = Vec::with_capacity(ids.len());
+		for id in ids {
+			if let Some(entry) = statement.query_row(params![id], row_to_entry).optional()? {
+				entries.push(entry);
+			}
+		}
 		Ok(entries)
 	}
+
+	/// a single entry with its full payload and embedding
+	pub fn load_entry(&self, id: i64) -> rusqlite::Result<Option<ClipboardEntry>> {
+		self.conn.query_row(
+			&format!("SELECT {ENTRY_COLUMNS} FROM clipboard_history WHERE id = ?1"),
+			params![id],
+			row_to_entry,
+		).optional()
+	}
+
+	/// the list thumbnail for an image entry, falling back to the full image when none could be generated
+	pub fn load_thumbnail(&self, id: i64) -> rusqlite::Result<Option<Vec<u8>>> {
+		self.conn.query_row(
+			"SELECT COALESCE(thumbnail, image_data) FROM clipboard_history WHERE id = ?1 AND content_type = 'image'",
+			params![id],
+			|row| row.get(0),
+		).optional().map(Option::flatten)
+	}
+
+	/// the full png for an image entry
+	pub fn load_image(&self, id: i64) -> rusqlite::Result<Option<Vec<u8>>> {
+		self.conn.query_row(
+			"SELECT image_data FROM clipboard_history WHERE id = ?1 AND content_type = 'image'",
+			params![id],
+			|row| row.get(0),
+		).optional().map(Option::flatten)
+	}
+
+	/// stream every stored embedding as (id, is_image, embedding) without keeping them in memory
+	pub fn scan_embeddings(&self, mut f: impl FnMut(i64, bool, &[f32])) -> rusqlite::Result<()> {
+		let mut statement = self.conn.prepare(
+			"SELECT id, content_type, embedding FROM clipboard_history WHERE embedding IS NOT NULL AND length(embedding) > 0"
+		)?;
+		let mut rows = statement.query([])?;
+		while let Some(row) = rows.next()? {
+			let content_type: String = row.get(1)?;
+			let emb_bytes: Vec<u8> = row.get(2)?;
+			f(row.get(0)?, content_type == "image", &bytes_to_embedding(&emb_bytes));
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use chrono::Duration;
+
+	fn entry(content: ClipboardContent, minutes_ago: i64) -> ClipboardEntry {
+		ClipboardEntry {
+			id: 0,
+			content,
+			copied_at: Local::now() - Duration::minutes(minutes_ago),
+			embedding: Some(vec![1.0]),
+			use_count: 1,
+			pinned: false,
+			expires_at: None,
+			source: CaptureSource::Clipboard,
+			app: None,
+		}
+	}
+
+	fn memory_db() -> Database {
+		Database::from_connection(Connection::open_in_memory().unwrap()).unwrap()
+	}
+
+	fn png(width: u32, height: u32) -> ClipboardContent {
+		let mut png = Vec::new();
+		image::RgbaImage::new(width, height).write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+		ClipboardContent::Image(ClipboardImage::from_png(png))
+	}
+
+	fn unlimited() -> RetentionPolicy {
+		RetentionPolicy {
+			max_entries: None,
+			max_age_days: None,
+			max_size_mb: None,
+			max_images: None,
+			max_image_age_days: None,
+			max_image_size_mb: None,
+		}
+	}
+
+	#[test]
+	fn prune_keeps_newest_entries() {
+		let db = memory_db();
+		let ids: Vec<i64> = (0..5).map(|i| db.insert(&entry(ClipboardContent::Text(format!("entry {i}")), 10 - i)).unwrap()).collect();
+
+		let removed = db.prune(&RetentionPolicy { max_entries: Some(3), ..unlimited() }, Local::now()).unwrap();
+
+		assert_eq!(removed, ids[..2]);
+		assert_eq!(db.load_page(None, PAGE_SIZE, true).unwrap().len(), 3);
+		assert!(db.search_text("entry", 10).unwrap().iter().all(|m| !removed.contains(&m.id)));
+	}
+
+	#[test]
+	fn prune_drops_old_entries() {
+		let db = memory_db();
+		let old = db.insert(&entry(ClipboardContent::Text("old".to_string()), 60 * 24 * 8)).unwrap();
+		db.insert(&entry(ClipboardContent::Text("new".to_string()), 60)).unwrap();
+
+		let removed = db.prune(&RetentionPolicy { max_age_days: Some(7), ..unlimited() }, Local::now()).unwrap();
+
+		assert_eq!(removed, vec![old]);
+	}
+
+	#[test]
+	fn image_limits_leave_text_alone() {
+		let db = memory_db();
+		let text = db.insert(&entry(ClipboardContent::Text("old text".to_string()), 30)).unwrap();
+		let old_image = db.insert(&entry(png(1, 1), 20)).unwrap();
+		let new_image = db.insert(&entry(png(2, 2), 10)).unwrap();
+
+		let removed = db.prune(&RetentionPolicy { max_images: Some(1), ..unlimited() }, Local::now()).unwrap();
+		assert_eq!(removed, vec![old_image]);
+
+		let removed = db.prune(&RetentionPolicy { max_image_age_days: Some(0), ..unlimited() }, Local::now()).unwrap();
+		assert_eq!(removed, vec![new_image]);
+		assert!(db.load_entry(text).unwrap().is_some());
+	}
+
+	#[test]
+	fn prune_caps_total_size() {
+		let db = memory_db();
+		let big = "x".repeat(600 * 1024);
+		let oldest = db.insert(&entry(ClipboardContent::Text(format!("a{big}")), 30)).unwrap();
+		db.insert(&entry(ClipboardContent::Text(format!("b{big}")), 20)).unwrap();
+
+		let removed = db.prune(&RetentionPolicy { max_size_mb: Some(1), ..unlimited() }, Local::now()).unwrap();
+
+		assert_eq!(removed, vec![oldest]);
+		assert!(db.vacuum_if_needed().unwrap());
+	}
+
+	#[test]
+	fn prune_never_removes_pinned_entries() {
+		let db = memory_db();
+		let pinned = db.insert(&entry(ClipboardContent::Text("keep me".to_string()), 60 * 24 * 30)).unwrap();
+		db.set_pinned(pinned, true).unwrap();
+		let unpinned: Vec<i64> = (0..3).map(|i| db.insert(&entry(ClipboardContent::Text(format!("entry {i}")), 10 - i)).unwrap()).collect();
+
+		let policy = RetentionPolicy { max_entries: Some(2), max_age_days: Some(7), ..unlimited() };
+		let removed = db.prune(&policy, Local::now()).unwrap();
+
+		assert_eq!(removed, vec![unpinned[0]]);
+		assert_eq!(db.load_pinned().unwrap().iter().map(|e| e.id).collect::<Vec<_>>(), vec![pinned]);
+		assert!(db.load_page(None, PAGE_SIZE, true).unwrap().iter().all(|e| !e.pinned));
+	}
+
+	#[test]
+	fn delete_expired_removes_only_due_entries() {
+		let db = memory_db();
+		let now = Local::now();
+		let due = db.insert(&ClipboardEntry { expires_at: Some(now - Duration::minutes(1)), ..entry(ClipboardContent::Text("due".to_string()), 5) }).unwrap();
+		let pinned = db.insert(&ClipboardEntry { expires_at: Some(now - Duration::minutes(1)), ..entry(ClipboardContent::Text("pinned".to_string()), 5) }).unwrap();
+		db.set_pinned(pinned, true).unwrap();
+		let later = db.insert(&ClipboardEntry { expires_at: Some(now + Duration::minutes(10)), ..entry(ClipboardContent::Text("later".to_string()), 5) }).unwrap();
+		let kept = db.insert(&entry(ClipboardContent::Text("kept".to_string()), 5)).unwrap();
+
+		assert_eq!(db.delete_expired(now).unwrap(), vec![due]);
+		assert!(db.load_entry(pinned).unwrap().is_some());
+		assert!(db.load_entry(later).unwrap().is_some());
+		assert!(db.load_entry(kept).unwrap().is_some());
+		assert!(db.search_text("due", 10).unwrap().is_empty());
+	}
+
+	#[test]
+	fn clear_history_keeps_pinned_entries() {
+		let db = memory_db();
+		let pinned = db.insert(&entry(ClipboardContent::Text("pinned note".to_string()), 5)).unwrap();
+		db.set_pinned(pinned, true).unwrap();
+		let cleared: Vec<i64> = (0..2).map(|i| db.insert(&entry(ClipboardContent::Text(format!("note {i}")), 2 - i)).unwrap()).collect();
+
+		assert_eq!(db.clear_history().unwrap(), cleared);
+		assert!(db.load_page(None, PAGE_SIZE, true).unwrap().is_empty());
+		assert_eq!(db.search_text("note", 10).unwrap().iter().map(|hit| hit.id).collect::<Vec<_>>(), vec![pinned]);
+	}
+
+	#[test]
+	fn pages_walk_history_newest_first() {
+		let db = memory_db();
+		let mut ids = Vec::new();
+		for i in 0..5 {
+			ids.push(db.insert(&entry(ClipboardContent::Text(format!("entry {i}")), 10 - i)).unwrap());
+		}
+		ids.reverse();
+
+		let mut seen = Vec::new();
+		let mut cursor = None;
+		loop {
+			let page = db.load_page(cursor, 2, true).unwrap();
+			if page.is_empty() {
+				break;
+			}
+			cursor = page.last().map(|e| e.cursor());
+			seen.extend(page.iter().map(|e| e.id));
+		}
+
+		assert_eq!(seen, ids);
+	}
+
+	#[test]
+	fn pages_can_leave_out_primary_selections() {
+		let db = memory_db();
+		let copied = db.insert(&entry(ClipboardContent::Text("copied".to_string()), 2)).unwrap();
+		let selected = db.insert(&ClipboardEntry { source: CaptureSource::Primary, ..entry(ClipboardContent::Text("selected".to_string()), 1) }).unwrap();
+
+		let ids = |include_primary| db.load_page(None, PAGE_SIZE, include_primary).unwrap().iter().map(|e| e.id).collect::<Vec<_>>();
+		assert_eq!(ids(true), vec![selected, copied]);
+		assert_eq!(ids(false), vec![copied]);
+
+		// copying the selection for real turns it into a clipboard entry
+		db.touch_existing(&ClipboardContent::Text("selected".to_string()), CaptureSource::Clipboard, None, Local::now(), None).unwrap();
+		assert_eq!(ids(false), vec![selected, copied]);
+	}
+
+	#[test]
+	fn app_filters_are_split_from_the_query() {
+		assert_eq!(split_app_filter("app:firefox invoice"), (Some("firefox".to_string()), "invoice".to_string()));
+		assert_eq!(split_app_filter("  tax   app:code  2024 "), (Some("code".to_string()), "tax 2024".to_string()));
+		assert_eq!(split_app_filter("app: invoice"), (None, "app: invoice".to_string()));
+		assert_eq!(split_app_filter("invoice"), (None, "invoice".to_string()));
+	}
+
+	#[test]
+	fn source_app_is_stored_and_follows_the_latest_copy() {
+		let db = memory_db();
+		let firefox = SourceApp { name: "firefox".to_string(), title: "Invoices — Mozilla Firefox".to_string(), class: String::new() };
+		let id = db.insert(&ClipboardEntry { app: Some(firefox.clone()), ..entry(ClipboardContent::Text("invoice 42".to_string()), 5) }).unwrap();
+		db.insert(&entry(ClipboardContent::Text("unknown origin".to_string()), 4)).unwrap();
+		assert_eq!(db.load_entry(id).unwrap().unwrap().app, Some(firefox.clone()));
+
+		// an unknown app doesn't wipe what's known
+		let text = ClipboardContent::Text("invoice 42".to_string());
+		let touched = db.touch_existing(&text, CaptureSource::Clipboard, None, Local::now(), None).unwrap().unwrap();
+		assert_eq!(touched.app, Some(firefox));
+
+		let terminal = SourceApp { name: "alacritty".to_string(), title: String::new(), class: String::new() };
+		let touched = db.touch_existing(&text, CaptureSource::Clipboard, Some(&terminal), Local::now(), None).unwrap().unwrap();
+		assert_eq!(touched.app, Some(terminal));
+
+		let from_app = |app| db.load_from_app(app, 10).unwrap().iter().map(|e| e.id).collect::<Vec<_>>();
+		assert_eq!(from_app("Alac"), [id]);
+		assert!(from_app("firefox").is_empty());
+	}
+
+	#[test]
+	fn ignored_apps_are_kept_once_regardless_of_case() {
+		let db = memory_db();
+		db.add_ignored_app("KeePassXC").unwrap();
+		db.add_ignored_app("keepassxc").unwrap();
+		db.add_ignored_app("*term*").unwrap();
+		assert_eq!(db.load_ignored_apps().unwrap(), ["*term*", "KeePassXC"]);
+
+		db.remove_ignored_app("KEEPASSXC").unwrap();
+		assert_eq!(db.load_ignored_apps().unwrap(), ["*term*"]);
+	}
+
+	#[test]
+	fn rich_formats_round_trip() {
+		let db = memory_db();
+		let contents = [
+			ClipboardContent::Html { html: "<b>bold</b> move".to_string(), text: "bold move".to_string() },
+			ClipboardContent::Rtf { rtf: r"{\rtf1 \b bold\b0}".to_string(), text: "bold".to_string() },
+			ClipboardContent::Files(vec![PathBuf::from("/home/me/a.txt"), PathBuf::from("/home/me/b c.png")]),
+			ClipboardContent::Url("https://example.com/page".to_string()),
+		];
+		for content in &contents {
+			let id = db.insert(&entry(content.clone(), 1)).unwrap();
+			assert_eq!(&db.load_entry(id).unwrap().unwrap().content, content);
+		}
+
+		// pages leave the markup out but keep the text for rendering
+		let page = db.load_page(None, PAGE_SIZE, true).unwrap();
+		assert!(page.iter().any(|e| e.content == ClipboardContent::Html { html: String::new(), text: "bold move".to_string() }));
+		// formatting doesn't get in the way of keyword search
+		assert_eq!(db.search_text("move", 10).unwrap().len(), 1);
+		assert_eq!(db.search_text("b c", 10).unwrap().len(), 1);
+	}
+
+	#[test]
+	fn pages_leave_out_image_payloads() {
+		let db = memory_db();
+		let mut png = Vec::new();
+		image::RgbaImage::new(800, 600).write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+		let image = ClipboardContent::Image(ClipboardImage::from_png(png.clone()));
+		let id = db.insert(&entry(image.clone(), 0)).unwrap();
+
+		let page = db.load_page(None, PAGE_SIZE, true).unwrap();
+		assert_eq!(page[0].content, ClipboardContent::Image(ClipboardImage { png: Vec::new(), width: 800, height: 600, byte_size: png.len() }));
+		assert_eq!(page[0].embedding, None);
+
+		assert_eq!(db.load_entry(id).unwrap().unwrap().content, image);
+		assert_eq!(db.load_image(id).unwrap(), Some(png));
+
+		let thumbnail = image::load_from_memory(&db.load_thumbnail(id).unwrap().unwrap()).unwrap();
+		assert_eq!((thumbnail.width(), thumbnail.height()), (512, 384));
+	}
 }
@@ -1,38 +1,48 @@
 use fastembed::{EmbeddingModel, ImageEmbedding, ImageEmbeddingModel, ImageInitOptions, InitOptions, TextEmbedding};
+use crate::settings::SearchModel;
 
 pub type Embedding = Vec<f32>;
 
 pub struct Embedder {
 	text_model: TextEmbedding,
 	image_model: ImageEmbedding,
+	/// nomic models are trained with task prefixes, other models don't know them
+	task_prefixes: bool,
 }
 
 impl Embedder {
-	pub fn new() -> anyhow::Result<Self> {
+	pub fn new(model: SearchModel) -> anyhow::Result<Self> {
+		let (text, image) = match model {
+			SearchModel::NomicV15 => (EmbeddingModel::NomicEmbedTextV15, ImageEmbeddingModel::NomicEmbedVisionV15),
+			SearchModel::NomicV15Quantized => (EmbeddingModel::NomicEmbedTextV15Q, ImageEmbeddingModel::NomicEmbedVisionV15),
+			SearchModel::ClipVitB32 => (EmbeddingModel::ClipVitB32, ImageEmbeddingModel::ClipVitB32),
+		};
+
 		let text_model = TextEmbedding::try_new(
-			InitOptions::new(EmbeddingModel::NomicEmbedTextV15).with_show_download_progress(true),
+			InitOptions::new(text).with_show_download_progress(true),
 		)?;
 
 		let image_model = ImageEmbedding::try_new(
-			ImageInitOptions::new(ImageEmbeddingModel::NomicEmbedVisionV15).with_show_download_progress(true),
+			ImageInitOptions::new(image).with_show_download_progress(true),
 		)?;
 
 		Ok(Self {
 			text_model,
 			image_model,
+			task_prefixes: model != SearchModel::ClipVitB32,
 		})
 	}
 
 	/// embed copied text, add `search_document:` prefix
 	pub fn embed_document(&mut self, text: &str) -> anyhow::Result<Embedding> {
-		let prefixed = format!("search_document: {text}");
+		let prefixed = if self.task_prefixes { format!("search_document: {text}") } else { text.to_string() };
 		let embeddings = self.text_model.embed(vec![&prefixed], None)?;
 		embeddings.into_iter().next().ok_or_else(|| anyhow::anyhow!("No embedding returned"))
 	}
 
 	/// embed search query text, add `search_query:` prefix
 	pub fn embed_query(&mut self, text: &str) -> anyhow::Result<Embedding> {
-		let prefixed = format!("search_query: {text}");
+		let prefixed = if self.task_prefixes { format!("search_query: {text}") } else { text.to_string() };
 		let embeddings = self.text_model.embed(vec![&prefixed], None)?;
 		embeddings.into_iter().next().ok_or_else(|| anyhow::anyhow!("No embedding returned"))
 	}
@@ -43,8 +53,11 @@ impl Embedder {
 		embeddings.into_iter().next().ok_or_else(|| anyhow::anyhow!("No embedding returned"))
 	}
 
-	/// cosine similarity
+	/// cosine similarity, 0 for embeddings from different models
 	pub fn similarity(a: &[f32], b: &[f32]) -> f32 {
+		if a.len() != b.len() {
+			return 0.0;
+		}
 		let dot: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
 		let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
 		let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use global_hotkey::hotkey::HotKey;
+
+/// what a global shortcut can be bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HotkeyAction {
+	OpenMainWindow,
+	QuickPaste,
+	/// put the entry before the newest one back on the clipboard and paste it into the focused window
+	PastePrevious,
+	TogglePause,
+	/// put what's on the clipboard back without its formatting and paste it
+	PastePlainText,
+}
+
+impl HotkeyAction {
+	pub const ALL: [HotkeyAction; 5] = [
+		HotkeyAction::OpenMainWindow,
+		HotkeyAction::QuickPaste,
+		HotkeyAction::PastePrevious,
+		HotkeyAction::TogglePause,
+		HotkeyAction::PastePlainText,
+	];
+
+	pub fn label(self) -> &'static str {
+		match self {
+			HotkeyAction::OpenMainWindow => "Open main window",
+			HotkeyAction::QuickPaste => "Quick paste",
+			HotkeyAction::PastePrevious => "Paste previous entry",
+			HotkeyAction::TogglePause => "Pause capture",
+			HotkeyAction::PastePlainText => "Paste as plain text",
+		}
+	}
+}
+
+/// parsed shortcuts for the bound actions, and what's wrong with the rest
+/// empty accelerators leave an action unbound, the first action to claim a shortcut keeps it
+pub fn resolve<'a>(bindings: impl IntoIterator<Item = (HotkeyAction, &'a str)>) -> (Vec<(HotkeyAction, HotKey)>, Vec<String>) {
+	let mut resolved = Vec::new();
+	let mut problems = Vec::new();
+	let mut claimed: HashMap<u32, HotkeyAction> = HashMap::new();
+	for (action, accelerator) in bindings {
+		let accelerator = accelerator.trim();
+		if accelerator.is_empty() {
+			continue;
+		}
+		let hotkey = match HotKey::from_str(accelerator) {
+			Ok(hotkey) => hotkey,
+			Err(err) => {
+				problems.push(format!("{}: `{accelerator}` isn't a valid shortcut ({err})", action.label()));
+				continue;
+			}
+		};
+		if let Some(owner) = claimed.get(&hotkey.id()) {
+			problems.push(format!("{}: `{accelerator}` is already bound to {}", action.label(), owner.label()));
+			continue;
+		}
+		claimed.insert(hotkey.id(), action);
+		resolved.push((action, hotkey));
+	}
+	(resolved, problems)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn empty_bindings_are_left_out() {
+		let (resolved, problems) = resolve([(HotkeyAction::QuickPaste, "Ctrl+Shift+KeyV"), (HotkeyAction::PastePrevious, " ")]);
+		assert_eq!(resolved.iter().map(|(action, _)| *action).collect::<Vec<_>>(), [HotkeyAction::QuickPaste]);
+		assert!(problems.is_empty());
+	}
+
+	#[test]
+	fn conflicts_name_both_actions() {
+		// the same shortcut spelled differently is still the same shortcut
+		let (resolved, problems) = resolve([(HotkeyAction::QuickPaste, "Ctrl+Shift+KeyV"), (HotkeyAction::PastePlainText, "shift+control+v")]);
+		assert_eq!(resolved.len(), 1);
+		assert_eq!(problems, ["Paste as plain text: `shift+control+v` is already bound to Quick paste"]);
+	}
+
+	#[test]
+	fn invalid_shortcuts_are_reported() {
+		let (resolved, problems) = resolve([(HotkeyAction::TogglePause, "Ctrl+Banana")]);
+		assert!(resolved.is_empty());
+		assert!(problems[0].starts_with("Pause capture: `Ctrl+Banana` isn't a valid shortcut"), "{problems:?}");
+	}
+}
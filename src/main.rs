@@ -1,73 +1,267 @@
 #![allow(non_snake_case)] // uppercase component function names
 mod db;
 mod embed;
+mod migrations;
+mod retention;
+mod sensitive;
 mod monitor;
+mod backend;
+mod capture;
+mod capture_hints;
+mod primary;
+mod source_app;
 mod clipboard_view;
 mod titlebar;
 mod quick_paste;
+mod tray;
+mod settings;
+mod settings_view;
+mod hotkeys;
+mod paste;
+mod placement;
+mod transform;
 
 use chrono::Local;
-use db::{ClipboardEntry, Database};
+use db::{ClipboardEntry, Database, SharedDatabase, TextMatch, PAGE_SIZE};
+#[cfg(windows)]
 use dioxus::desktop::tao::platform::windows::WindowBuilderExtWindows;
 use dioxus::prelude::*;
 use dioxus::desktop::{Config, WindowBuilder, trayicon};
-use dioxus::desktop::{use_global_shortcut, use_tray_icon_event_handler, use_tray_menu_event_handler, HotKeyState};
+use dioxus::desktop::{use_asset_handler, use_tray_icon_event_handler, use_tray_menu_event_handler, DesktopContext, HotKeyState, ShortcutHandle, ShortcutRegistryError};
+use dioxus::desktop::wry::http::Response;
 use embed::Embedder;
-use monitor::ClipboardContent;
+use hotkeys::HotkeyAction;
+use backend::{ClipboardWriteSuppression, SharedBackend, SystemClipboard};
+use capture::{CapturePipeline, Stored};
+use monitor::{CaptureControls, CaptureSource, CaptureState, ClipboardContent};
+use paste::{PasteTarget, Paster, SharedPaster};
+use settings::{Settings, SettingsWatcher};
+use source_app::SourceAppReader;
+use transform::Transform;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use crate::clipboard_view::ClipboardView;
-use crate::quick_paste::{ClipboardWriteSuppression, write_clipboard_content};
 use crate::titlebar::Titlebar;
-use crate::quick_paste::{QuickPaste, QuickPasteProps, quick_paste_config};
+use crate::quick_paste::{QuickPaste, QuickPasteShared, popup_position, quick_paste_config};
+use crate::tray::TrayAction;
+use crate::settings_view::SettingsView;
+use crate::clipboard_view::IMAGE_ASSET_HANDLER;
 
 const TAILWIND_CSS: Asset = asset!("/assets/tailwind.css");
 
 fn main() {
-    let cfg = Config::new()
-        .with_window(
-            WindowBuilder::new()
-                .with_decorations(false)
-                .with_transparent(true)
-				.with_undecorated_shadow(false)
-                .with_title("shadowpaste")
-                .with_resizable(true)
-        )
-        .with_close_behaviour(dioxus::desktop::WindowCloseBehaviour::WindowHides);
-
-    LaunchBuilder::desktop().with_cfg(cfg).launch(App);
+	let window = WindowBuilder::new()
+		.with_decorations(false)
+		.with_transparent(true)
+		.with_title("shadowpaste")
+		.with_resizable(true);
+	// windows draws its own shadow around undecorated windows
+	#[cfg(windows)]
+	let window = window.with_undecorated_shadow(false);
+
+	let cfg = Config::new()
+		.with_window(window)
+		.with_close_behaviour(dioxus::desktop::WindowCloseBehaviour::WindowHides);
+
+	let builder = LaunchBuilder::desktop().with_cfg(cfg);
+	match Database::open() {
+		Ok(database) => builder.with_context(Arc::new(Mutex::new(database)) as SharedDatabase).launch(App),
+		// written by a newer build, or a migration failed and was rolled back
+		Err(err) => {
+			eprintln!("Failed to open database: {err:#}");
+			builder.with_context(DatabaseError(format!("{err:#}"))).launch(DatabaseErrorView)
+		}
+	}
 }
 
-/// get raw image bytes from base64 data URI like "data:image/png;base64,..."
-fn decode_data_uri(data_uri: &str) -> Option<Vec<u8>> {
-	let (_, b64) = data_uri.split_once(";base64,")?;
-	use base64::{Engine as _, engine::general_purpose};
-	general_purpose::STANDARD.decode(b64).ok()
+/// why the database couldn't be opened, shown instead of the app
+#[derive(Clone)]
+struct DatabaseError(String);
+
+fn DatabaseErrorView() -> Element {
+	fn quit() {
+		std::process::exit(1);
+	}
+
+	let DatabaseError(message) = use_context::<DatabaseError>();
+	let path = db::data_dir().join("shadowpaste.db");
+
+	rsx! {
+		Stylesheet { href: TAILWIND_CSS }
+		div { class: "h-screen w-screen bg-slate-950 text-slate-200 flex flex-col items-center justify-center gap-4 p-8 font-sans overflow-hidden rounded-xl border border-slate-800 shadow-2xl",
+			div { class: "text-4xl font-bold text-slate-700", "shadowpaste" }
+			div { class: "text-sm text-red-300 text-center max-w-lg", "The clipboard history couldn't be opened: {message}" }
+			div { class: "text-xs text-slate-500 text-center max-w-lg", "The database is at {path.display()}." }
+			button {
+				class: "px-4 py-1.5 rounded-lg bg-slate-800 hover:bg-slate-700 text-sm transition-colors",
+				onclick: |_| quit(),
+				"Quit"
+			}
+		}
+	}
 }
 
 /// compute embedding for clipboard content based on its type
 fn compute_embedding(embedder: &mut Embedder, content: &ClipboardContent) -> Option<Vec<f32>> {
 	match content {
-		ClipboardContent::Text(t) => embedder.embed_document(t).ok(),
-		ClipboardContent::Image(data_uri) => {
-			let bytes = decode_data_uri(data_uri)?;
-			embedder.embed_image_bytes(&bytes).ok()
-		}
-		ClipboardContent::Empty => None,
+		ClipboardContent::Image(image) => embedder.embed_image_bytes(&image.png).ok(),
+		_ => embedder.embed_document(&content.plain_text()?).ok(),
+	}
+}
+
+/// how many nearest embeddings are considered per search, on top of keyword hits
+const SEMANTIC_CANDIDATES: usize = 100;
+
+/// hybrid search over the whole database: keyword hits plus nearest embeddings, best first
+/// an `app:name` term narrows results down to copies made in that app
+/// returns (entry, similarity, keyword snippet)
+/// image similarities are multiplied by `image_weight` to compete with text ones
+fn search_history(db: &Mutex<Database>, query: &str, query_emb: Option<&[f32]>, image_weight: f32) -> Vec<(ClipboardEntry, f32, Option<String>)> {
+	let Ok(db_guard) = db.lock() else { return Vec::new(); };
+
+	let (app, query) = db::split_app_filter(query);
+	let from_app = |entry: &ClipboardEntry| match (&app, &entry.app) {
+		(None, _) => true,
+		(Some(filter), Some(source)) => source.name.to_lowercase().contains(&filter.to_lowercase()),
+		(Some(_), None) => false,
+	};
+	if let (Some(app), true) = (&app, query.is_empty()) {
+		let entries = db_guard.load_from_app(app, 500).unwrap_or_default();
+		return entries.into_iter().map(|e| (e, 0.0, None)).collect();
+	}
+	let query = query.as_str();
+
+	let text_hits: HashMap<i64, TextMatch> = db_guard.search_text(query, 500)
+		.unwrap_or_default()
+		.into_iter()
+		.map(|m| (m.id, m))
+		.collect();
+
+	// (id, similarity, similarity used for sorting)
+	let mut similarities: Vec<(i64, f32, f32)> = Vec::new();
+	if let Some(q_emb) = query_emb {
+		let _ = db_guard.scan_embeddings(|id, is_image, emb| {
+			let sim = Embedder::similarity(q_emb, emb);
+			// it seems like image similarity is about 10x less than text similarity?? even with the same model
+			let sim_for_score = if is_image { sim * image_weight } else { sim };
+			similarities.push((id, sim, sim_for_score));
+		});
+	}
+	similarities.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
+
+	let sim_by_id: HashMap<i64, (f32, f32)> = similarities.iter().map(|&(id, sim, score)| (id, (sim, score))).collect();
+	let mut candidates: Vec<i64> = text_hits.keys().copied().collect();
+	candidates.extend(similarities.iter().take(SEMANTIC_CANDIDATES).map(|&(id, _, _)| id).filter(|id| !text_hits.contains_key(id)));
+
+	let mut entries = db_guard.load_by_ids(&candidates).unwrap_or_default();
+	drop(db_guard);
+	entries.retain(from_app);
+
+	// (score_for_sorting, entry, similarity, snippet)
+	let mut scored: Vec<(f32, ClipboardEntry, f32, Option<String>)> = entries.into_iter().map(|e| {
+		let (emb_sim, emb_sim_for_score) = sim_by_id.get(&e.id).copied().unwrap_or((0.0, 0.0));
+		let text_hit = text_hits.get(&e.id);
+
+		// prefer actual text matches (better bm25 first, it's negative so flip it into 0..1), then similarity
+		let score = if let Some(hit) = text_hit {
+			let relevance = (-hit.rank / (1.0 - hit.rank)) as f32;
+			2.0 + relevance + emb_sim_for_score
+		} else {
+			emb_sim_for_score
+		};
+
+		(score, e, emb_sim, text_hit.map(|hit| hit.snippet.clone()))
+	}).collect();
+
+	scored.sort_by(|a, b| {
+		b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal)
+	});
+
+	scored.into_iter().map(|(_, entry, sim, snippet)| (entry, sim, snippet)).collect()
+}
+
+fn shortcut_error(err: ShortcutRegistryError) -> String {
+	match err {
+		ShortcutRegistryError::InvalidShortcut(shortcut) => format!("invalid shortcut {shortcut}"),
+		ShortcutRegistryError::Other(err) => err.to_string(),
+		_ => "unknown error".to_string(),
 	}
 }
 
 fn App() -> Element {
+	// loaded pages of history, newest first
 	let mut history = use_signal(|| Vec::<ClipboardEntry>::new());
+	// pinned entries are kept apart and always fully loaded
+	let mut pinned = use_signal(Vec::<ClipboardEntry>::new);
+	let mut has_more_history = use_signal(|| false);
 	let mut search_query = use_signal(|| String::new());
 	let mut loading_status = use_signal(|| "Loading embedding models...".to_string());
-	let clipboard_write_suppression: Signal<ClipboardWriteSuppression> = use_signal(|| Arc::new(Mutex::new(false)));
+	let clipboard_write_suppression: Signal<ClipboardWriteSuppression> = use_signal(ClipboardWriteSuppression::default);
+	// loaded from `settings.toml`, saved from the settings view and reloaded when the file changes
+	let mut settings_error = use_signal(|| None::<String>);
+	let mut settings = use_signal(|| Settings::load_or_create(&settings::settings_path()).unwrap_or_else(|err| {
+		eprintln!("{err:#}, using the defaults");
+		settings_error.set(Some(format!("{err:#}")));
+		Settings::default()
+	}));
+	let mut show_settings = use_signal(|| false);
+	// shared with the listener threads: selections, ignored apps and pausing
+	let capture_controls: Signal<CaptureControls> = use_signal(CaptureControls::default);
+	// middle-click selections are only recorded on request, and can be hidden from the list
+	let record_primary = use_memo(move || settings.read().capture.record_selections);
+	let mut show_primary = use_signal(|| true);
+	// copies made in these apps are never recorded, loaded from the database below
+	let mut ignored_patterns = use_signal(Vec::<String>::new);
+	let mut capture_state = use_signal(|| CaptureState::Recording);
+	let backend = use_signal(|| Arc::new(SystemClipboard::new(capture_controls.peek().clone())) as SharedBackend);
+	// quick paste types into the window it opened over
+	let paster = use_signal(|| Arc::new(Paster::new()) as SharedPaster);
+	let source_apps = use_signal(SourceAppReader::new);
+	use_effect(move || {
+		if let Ok(mut enabled) = capture_controls().primary.lock() {
+			*enabled = record_primary();
+		}
+	});
 	let mut context_menu = use_signal(|| None::<(i64, f64, f64)>);
+	// the context menu lists transformations instead of its usual items
+	let mut copy_as = use_signal(|| false);
 	let mut pending_delete = use_signal(|| None::<i64>);
+	let mut pending_clear = use_signal(|| false);
 	
 	let window = dioxus::desktop::use_window();
 
-	// initialize tray
-	let _tray = use_signal(|| trayicon::init_tray_icon(trayicon::default_tray_icon(), None));
+	// initialize tray, its menu lists the newest entries and its icon follows the capture state
+	let tray = use_signal(|| trayicon::init_tray_icon(tray::menu(CaptureState::Recording, &[]), tray::icon(CaptureState::Recording)));
+	use_effect(move || {
+		let menu = tray::menu(capture_state(), &history.read());
+		tray.peek().set_menu(Some(Box::new(menu)));
+	});
+	use_effect(move || {
+		let state = capture_state();
+		let tray = tray.peek();
+		if let Err(err) = tray.set_icon(tray::icon(state)).and_then(|_| tray.set_tooltip(Some(tray::tooltip(state)))) {
+			eprintln!("Failed to update the tray icon: {err}");
+		}
+	});
+
+	// stop recording until `until`, or until resumed, the listener checks on every change
+	let mut pause_capture = move |until: Option<chrono::DateTime<Local>>| {
+		let pause = capture_controls.peek().pause.clone();
+		pause.pause(until);
+		capture_state.set(pause.state());
+		// pick up the auto-resume, the listener already stops skipping by itself
+		if let Some(wait) = until.and_then(|until| (until - Local::now()).to_std().ok()) {
+			spawn(async move {
+				tokio::time::sleep(wait).await;
+				capture_state.set(pause.state());
+			});
+		}
+	};
+	let mut resume_capture = move || {
+		let pause = capture_controls.peek().pause.clone();
+		pause.resume();
+		capture_state.set(pause.state());
+	};
 
 	// click icon to restore window
 	let window_clone = window.clone();
@@ -82,42 +276,354 @@ fn App() -> Element {
 		}
 	});
 
-	// only a quit option in the tray
-	use_tray_menu_event_handler(move |_event| {
-		std::process::exit(0);
+	// load db and the first page of history
+	let opened = use_context::<SharedDatabase>();
+	let db: Signal<SharedDatabase> = use_signal(move || {
+		let Ok(database) = opened.lock() else {
+			return opened.clone();
+		};
+		let first_page = database.load_page(None, PAGE_SIZE, *show_primary.peek()).unwrap_or_default();
+		has_more_history.set(first_page.len() == PAGE_SIZE);
+		history.write().extend(first_page);
+		pinned.set(database.load_pinned().unwrap_or_default());
+		let patterns = database.load_ignored_apps().unwrap_or_default();
+		if let Ok(mut ignored) = capture_controls.peek().ignored.lock() {
+			ignored.clone_from(&patterns);
+		}
+		ignored_patterns.set(patterns);
+		drop(database);
+		opened
 	});
 
-	// ctrl+shift+v opens the quick-paste popup window
-	let window_for_hotkey = window.clone();
-	let history_for_hotkey = history;
-	let suppression_for_hotkey = clipboard_write_suppression();
-	let _ = use_global_shortcut(
-		"Ctrl+Shift+KeyV",
-		move |state| {
-			if state == HotKeyState::Pressed {
-				let w = window_for_hotkey.clone();
-				let mut entries = history_for_hotkey();
-				entries.reverse();
-				let suppression = suppression_for_hotkey.clone();
-				spawn(async move {
-					let dom = dioxus::core::VirtualDom::new_with_props(
-						QuickPaste,
-						QuickPasteProps { entries },
-					)
-					.with_root_context(suppression);
-					let popup = w.new_window(dom, quick_paste_config()).await;
-					popup.set_focus();
-				});
+	// put a stored entry back on the clipboard, as it was or rewritten by `transform`
+	// false when nothing was written
+	let copy_entry = move |id: i64, transform: Option<Transform>| -> bool {
+		let entry = db().lock().ok().and_then(|db_guard| db_guard.load_entry(id).ok().flatten());
+		let Some(entry) = entry else { return false; };
+		let content = match transform.map(|transform| transform.apply(&entry.content)) {
+			Some(Ok(content)) => content,
+			Some(Err(err)) => {
+				eprintln!("Failed to copy entry: {err:#}");
+				return false;
+			}
+			None => entry.content,
+		};
+		if let Err(err) = backend::write_suppressed(&*backend(), &clipboard_write_suppression(), id, &content) {
+			eprintln!("Failed to copy entry: {err}");
+			return false;
+		}
+		true
+	};
+	// the focused window and how to paste into it, `None` with auto-paste off
+	// has to be read before anything of ours takes focus
+	let paste_target = move || {
+		let settings = settings.peek();
+		settings
+			.paste
+			.auto_paste
+			.then(|| paster.peek().target(source_apps.peek().current().as_ref(), &settings.paste.shift_paste_apps))
+			.flatten()
+	};
+	// press the paste keys in `target` in the background, the way the quick paste popup does
+	let send_paste = move |target: Option<PasteTarget>| {
+		if let Some(target) = target {
+			let paster = paster.peek().clone();
+			tokio::task::spawn_blocking(move || {
+				if let Err(err) = paster.paste(target) {
+					eprintln!("Failed to paste into the focused window: {err:#}");
+				}
+			});
+		}
+	};
+	// copy an entry and paste it into the focused window
+	let paste_entry = move |id: i64| {
+		let target = paste_target();
+		if copy_entry(id, None) {
+			send_paste(target);
+		}
+	};
+	// put what's on the clipboard back without its formatting and paste it
+	// read from the clipboard, the newest entry is stale while capture is paused or after a skipped copy
+	let paste_plain_text = move || {
+		let target = paste_target();
+		let content = match backend().read().and_then(|content| transform::PLAIN_TEXT.apply(&content)) {
+			Ok(content) => content,
+			Err(err) => {
+				eprintln!("Failed to paste as plain text: {err:#}");
+				return;
+			}
+		};
+		// usually it's the newest entry, which then moves up rather than being recorded again
+		let text = content.plain_text();
+		let newest = pinned.peek().iter().chain(history.peek().iter().take(1)).max_by_key(|e| e.cursor()).filter(|e| e.content.plain_text() == text).map(|e| e.id);
+		let written = match newest {
+			Some(id) => backend::write_suppressed(&*backend(), &clipboard_write_suppression(), id, &content),
+			None => backend().write(&content),
+		};
+		match written {
+			Ok(()) => send_paste(target),
+			Err(err) => eprintln!("Failed to paste as plain text: {err}"),
+		}
+	};
+	// the `n`th newest entry, pinned or not
+	let nth_newest = move |n: usize| {
+		let mut newest: Vec<_> = pinned.peek().iter().chain(history.peek().iter().take(n + 1)).map(|e| (e.cursor(), e.id)).collect();
+		newest.sort_by_key(|&(cursor, _)| std::cmp::Reverse(cursor));
+		newest.get(n).map(|&(_, id)| id)
+	};
+
+	// tray menu items are told apart by their ids
+	let window_for_menu = window.clone();
+	use_tray_menu_event_handler(move |event| {
+		let show_window = || {
+			window_for_menu.set_visible(true);
+			window_for_menu.set_focus();
+		};
+		match TrayAction::from_id(event.id.as_ref()) {
+			Some(TrayAction::Copy(id)) => {
+				copy_entry(id, None);
+			}
+			Some(TrayAction::Open) => show_window(),
+			Some(TrayAction::Settings) => {
+				show_window();
+				show_settings.set(true);
+			}
+			Some(TrayAction::Pause(duration)) => pause_capture(duration.map(|duration| Local::now() + duration)),
+			Some(TrayAction::Resume) => resume_capture(),
+			Some(TrayAction::ClearHistory) => {
+				show_window();
+				pending_clear.set(true);
 			}
-		},
-	);
-
-	// load db
-	let db: Signal<Arc<Mutex<Database>>> = use_signal(|| {
-		let database = Database::open().expect("Failed to open database");
-		let existing = database.load_all().unwrap_or_default();
-		history.write().extend(existing);
-		Arc::new(Mutex::new(database))
+			Some(TrayAction::Quit) => std::process::exit(0),
+			None => {}
+		}
+	});
+
+	// one quick paste window for the whole session, created hidden up front so it opens instantly
+	let quick_paste = use_signal(QuickPasteShared::default);
+	let mut quick_paste_window = use_signal(|| None::<DesktopContext>);
+	let window_for_popup = window.clone();
+	use_hook(move || {
+		let dom = dioxus::core::VirtualDom::new(QuickPaste)
+			.with_root_context(clipboard_write_suppression())
+			.with_root_context(db())
+			.with_root_context(backend())
+			.with_root_context(paster())
+			.with_root_context(quick_paste());
+		let config = quick_paste_config(&settings.peek().popup);
+		spawn(async move {
+			quick_paste_window.set(Some(window_for_popup.new_window(dom, config).await));
+		});
+	});
+	// it lists the same entries as the main window
+	use_effect(move || quick_paste.peek().set_show_primary(show_primary()));
+	// it reloads its list whenever the history changes, reordering included
+	use_effect(move || {
+		history.read();
+		pinned.read();
+		quick_paste.peek().history_changed();
+	});
+
+	let mut run_hotkey = move |window: &DesktopContext, action: HotkeyAction| match action {
+		HotkeyAction::OpenMainWindow => {
+			window.set_visible(true);
+			window.set_focus();
+		}
+		HotkeyAction::QuickPaste => {
+			// still being created right after startup
+			let Some(popup) = quick_paste_window.peek().clone() else {
+				return;
+			};
+			// pressed again while it's open, it would be its own paste target
+			if popup.is_visible() {
+				popup.set_visible(false);
+				return;
+			}
+			// the popup is about to take focus, remember who had it and where its caret was
+			let target = paste_target();
+			let settings = settings.peek();
+			if let Some(position) = popup_position(window, &settings.popup) {
+				popup.set_outer_position(position);
+			}
+			popup.set_inner_size(dioxus::desktop::tao::dpi::LogicalSize::new(settings.popup.width, settings.popup.height));
+			quick_paste.peek().open(target);
+			popup.set_visible(true);
+			popup.set_focus();
+		}
+		HotkeyAction::PastePrevious => {
+			if let Some(id) = nth_newest(1) {
+				paste_entry(id);
+			}
+		}
+		HotkeyAction::TogglePause => {
+			if capture_controls.peek().pause.is_paused() {
+				resume_capture();
+			} else {
+				pause_capture(None);
+			}
+		}
+		HotkeyAction::PastePlainText => paste_plain_text(),
+	};
+
+	// global shortcuts from the settings, registered again whenever they change
+	// bad or clashing ones are listed instead of silently doing nothing
+	let hotkeys = use_memo(move || settings.read().hotkeys.clone());
+	let mut hotkey_handles = use_signal(Vec::<ShortcutHandle>::new);
+	let mut hotkey_errors = use_signal(Vec::<String>::new);
+	let window_for_hotkeys = window.clone();
+	use_effect(move || {
+		let hotkeys = hotkeys();
+		for handle in hotkey_handles.write().drain(..) {
+			handle.remove();
+		}
+
+		let (bindings, mut errors) = hotkeys::resolve(hotkeys.bindings());
+		for (action, hotkey) in bindings {
+			let window = window_for_hotkeys.clone();
+			let handler = move |state: HotKeyState| {
+				if state == HotKeyState::Pressed {
+					run_hotkey(&window, action);
+				}
+			};
+			match window_for_hotkeys.create_shortcut(hotkey, handler) {
+				Ok(handle) => hotkey_handles.write().push(handle),
+				Err(err) => errors.push(format!("{}: `{}` couldn't be registered, another app may be using it ({})", action.label(), hotkeys.binding(action), shortcut_error(err))),
+			}
+		}
+		for error in &errors {
+			eprintln!("Hotkey not registered: {error}");
+		}
+		hotkey_errors.set(errors);
+	});
+
+	// serve images straight from the database so they're only loaded when rendered
+	// paths look like `/clipboard-image/thumb/{id}` or `/clipboard-image/full/{id}`
+	use_asset_handler(IMAGE_ASSET_HANDLER, move |request, responder| {
+		let mut segments = request.uri().path().rsplit('/');
+		let id = segments.next().and_then(|id| id.parse::<i64>().ok());
+		let full = segments.next() == Some("full");
+		let db = db();
+		let bytes = id.and_then(|id| {
+			let db_guard = db.lock().ok()?;
+			let bytes = if full { db_guard.load_image(id) } else { db_guard.load_thumbnail(id) };
+			bytes.ok().flatten()
+		});
+		let response = match bytes {
+			Some(bytes) => Response::builder().header("Content-Type", "image/png").body(bytes),
+			None => Response::builder().status(404).body(Vec::new()),
+		};
+		if let Ok(response) = response {
+			responder.respond(response);
+		}
+	});
+
+	// fetch the next page when scrolling reaches the end of the list
+	let mut load_more_history = move || {
+		if !has_more_history() {
+			return;
+		}
+		let cursor = history.read().last().map(|e| e.cursor());
+		let page = db().lock().ok().and_then(|db_guard| db_guard.load_page(cursor, PAGE_SIZE, show_primary()).ok()).unwrap_or_default();
+		has_more_history.set(page.len() == PAGE_SIZE);
+		history.write().extend(page);
+	};
+
+	// showing or hiding selections changes what the pages contain, start over from the first one
+	let mut toggle_show_primary = move || {
+		show_primary.toggle();
+		history.write().clear();
+		has_more_history.set(true);
+		load_more_history();
+	};
+
+	let retention_policy = use_memo(move || settings.read().retention.policy());
+	let sensitive_policy = use_memo(move || settings.read().sensitive.policy());
+
+	// pick up edits made to the settings file outside the app
+	use_effect(move || {
+		spawn(async move {
+			let path = settings::settings_path();
+			let mut watcher = SettingsWatcher::new(path.clone());
+			loop {
+				tokio::time::sleep(settings::RELOAD_INTERVAL).await;
+				if !watcher.changed() {
+					continue;
+				}
+				match Settings::load_or_create(&path) {
+					Ok(reloaded) => {
+						settings_error.set(None);
+						if reloaded != *settings.peek() {
+							settings.set(reloaded);
+						}
+					}
+					Err(err) => {
+						eprintln!("{err:#}, keeping the previous settings");
+						settings_error.set(Some(format!("{err:#}")));
+					}
+				}
+			}
+		});
+	});
+
+	// validate and write, the watcher then sees a file that matches and leaves it be
+	let mut save_settings = move |updated: Settings| {
+		match updated.save(&settings::settings_path()) {
+			Ok(()) => {
+				settings_error.set(None);
+				settings.set(updated);
+			}
+			Err(err) => settings_error.set(Some(format!("{err:#}"))),
+		}
+	};
+	let mut toggle_record_primary = move || {
+		let mut updated = settings();
+		updated.capture.record_selections = !updated.capture.record_selections;
+		save_settings(updated);
+	};
+
+	// enforce retention in the background too, so limits apply even when nothing new is copied
+	use_effect(move || {
+		let db = db();
+		spawn(async move {
+			loop {
+				let db = db.clone();
+				let policy = retention_policy.peek().clone();
+				let pruned = tokio::task::spawn_blocking(move || {
+					let db_guard = db.lock().ok()?;
+					let pruned = db_guard.prune(&policy, Local::now()).ok()?;
+					if let Err(err) = db_guard.vacuum_if_needed() {
+						eprintln!("Failed to vacuum database: {err}");
+					}
+					Some(pruned)
+				}).await.ok().flatten().unwrap_or_default();
+
+				if !pruned.is_empty() {
+					history.write().retain(|e| !pruned.contains(&e.id));
+				}
+
+				tokio::time::sleep(retention::PRUNE_INTERVAL).await;
+			}
+		});
+	});
+
+	// flagged entries can expire within minutes, so check far more often than the retention task
+	use_effect(move || {
+		let db = db();
+		spawn(async move {
+			loop {
+				let db = db.clone();
+				let expired = tokio::task::spawn_blocking(move || {
+					db.lock().ok()?.delete_expired(Local::now()).ok()
+				}).await.ok().flatten().unwrap_or_default();
+
+				if !expired.is_empty() {
+					history.write().retain(|e| !expired.contains(&e.id));
+					pinned.write().retain(|e| !expired.contains(&e.id));
+				}
+
+				tokio::time::sleep(sensitive::EXPIRY_CHECK_INTERVAL).await;
+			}
+		});
 	});
 
 	let mut embedder: Signal<Option<Arc<Mutex<Embedder>>>> = use_signal(|| None);
@@ -125,7 +631,8 @@ fn App() -> Element {
 	// load embedding models in the background
 	use_effect(move || {
 		spawn(async move {
-			let result = tokio::task::spawn_blocking(|| Embedder::new()).await;
+			let model = settings.peek().search.model;
+			let result = tokio::task::spawn_blocking(move || Embedder::new(model)).await;
 
 			let emb = match result {
 				Ok(Ok(e)) => Arc::new(Mutex::new(e)),
@@ -145,65 +652,63 @@ fn App() -> Element {
 
 	// wait for typing to settle before embedding search query
 	let query_embedding = use_resource(move || async move {
-		let trimmed = search_query().trim().to_string();
+		let (_, trimmed) = db::split_app_filter(&search_query());
 		let emb_opt = embedder();
 		if trimmed.is_empty() { return None; }
 		let Some(emb_arc) = emb_opt else { return None; };
 
-		tokio::time::sleep(std::time::Duration::from_millis(350)).await;
+		tokio::time::sleep(std::time::Duration::from_millis(settings.peek().search.debounce_ms)).await;
 
 		tokio::task::spawn_blocking(move || {
 			emb_arc.lock().ok().and_then(|mut g| g.embed_query(&trimmed).ok())
 		}).await.ok().flatten()
 	});
 
+	// rank matches across the whole database, re-run when new entries arrive
+	let search_results = use_resource(move || async move {
+		let query = search_query().trim().to_string();
+		let query_emb = query_embedding().flatten();
+		let image_weight = settings.read().search.image_similarity_weight;
+		history.read();
+		if query.is_empty() {
+			return Vec::new();
+		}
+		let db = db();
+		tokio::task::spawn_blocking(move || search_history(&db, &query, query_emb.as_deref(), image_weight)).await.unwrap_or_default()
+	});
+
 	// start clipboard listener
 	use_effect(move || {
-		let mut rx = monitor::start_listener();
-		let db = db().clone();
-		let suppression = clipboard_write_suppression();
+		let mut rx = backend().subscribe();
+		let mut pipeline = CapturePipeline::new(db(), clipboard_write_suppression());
 
 		spawn(async move {
-			while let Some(content) = rx.recv().await {
-				let should_skip = if let Ok(mut suppressed) = suppression.lock() {
-					if *suppressed {
-						*suppressed = false;
-						true
-					} else {
-						false
-					}
-				} else {
-					false
-				};
-
-				if should_skip {
-					continue;
-				}
+			while let Some(capture) = rx.recv().await {
+				let source = capture.source;
+				let stored = pipeline.process(capture, &sensitive_policy.peek(), &retention_policy.peek(), |content| {
+					let emb_arc = embedder()?;
+					let mut emb_guard = emb_arc.lock().ok()?;
+					compute_embedding(&mut emb_guard, content)
+				});
 
-				let emb = if let Some(ref emb_arc) = embedder() {
-					if let Ok(mut emb_guard) = emb_arc.lock() {
-						compute_embedding(&mut emb_guard, &content)
-					} else {
-						None
+				match stored {
+					None => {}
+					Some(Stored::Existing(existing)) => {
+						let visible = existing.pinned || show_primary() || existing.source != CaptureSource::Primary;
+						let mut list = if existing.pinned { pinned.write() } else { history.write() };
+						list.retain(|e| e.id != existing.id);
+						if visible {
+							list.insert(0, existing.into_page_entry());
+						}
 					}
-				} else {
-					None
-				};
-
-				let mut entry = ClipboardEntry {
-					id: 0,
-					content,
-					copied_at: Local::now(),
-					embedding: emb,
-				};
-
-				if let Ok(db_guard) = db.lock() {
-					if let Ok(row_id) = db_guard.insert(&entry) {
-						entry.id = row_id;
+					Some(Stored::New { entry, removed }) => {
+						let mut hist = history.write();
+						hist.retain(|e| !removed.contains(&e.id));
+						if show_primary() || source != CaptureSource::Primary {
+							hist.insert(0, entry.into_page_entry());
+						}
 					}
 				}
-
-				history.write().push(entry);
 			}
 		});
 	});
@@ -214,8 +719,73 @@ fn App() -> Element {
 	};
 
 	let on_context_menu_request = move |(id, x, y): (i64, f64, f64)| {
+		copy_as.set(false);
 		context_menu.set(Some((id, x, y)));
 	};
+	// the source app and usable transforms of the entry under the context menu, looked up once per opening
+	let context_entry = use_memo(move || {
+		let (id, _, _) = context_menu()?;
+		let entry = db().lock().ok().and_then(|db_guard| db_guard.load_by_ids(&[id]).ok()).and_then(|mut entries| entries.pop())?;
+		// only what works on this entry is offered, e.g. no json formatting for prose
+		let transforms: Vec<Transform> = transform::TRANSFORMS.into_iter().filter(|transform| transform.apply(&entry.content).is_ok()).collect();
+		Some((entry.app, transforms))
+	});
+
+	// stop or resume recording an app from now on, what it already copied stays
+	let mut set_app_ignored = move |pattern: String, ignore: bool| {
+		let db = db();
+		let Ok(db_guard) = db.lock() else { return; };
+		let result = if ignore { db_guard.add_ignored_app(&pattern) } else { db_guard.remove_ignored_app(&pattern) };
+		if let Err(err) = result {
+			eprintln!("Failed to update ignored apps: {err}");
+			return;
+		}
+		let patterns = db_guard.load_ignored_apps().unwrap_or_default();
+		if let Ok(mut ignored) = capture_controls().ignored.lock() {
+			ignored.clone_from(&patterns);
+		}
+		ignored_patterns.set(patterns);
+	};
+
+	// move an entry between the pinned section and the history pages
+	let mut set_pinned = move |id: i64, pin: bool| {
+		let db = db();
+		let Ok(db_guard) = db.lock() else { return; };
+		if let Err(err) = db_guard.set_pinned(id, pin) {
+			eprintln!("Failed to update pin: {err}");
+			return;
+		}
+		pinned.set(db_guard.load_pinned().unwrap_or_default());
+
+		if pin {
+			history.write().retain(|e| e.id != id);
+		} else if let Some(entry) = db_guard.load_by_ids(&[id]).ok().and_then(|mut v| v.pop()) {
+			// only slot it back in if it falls within the pages loaded so far
+			let mut hist = history.write();
+			let within_loaded = !has_more_history() || hist.last().is_some_and(|last| entry.cursor() >= last.cursor());
+			if within_loaded {
+				hist.push(entry.into_page_entry());
+				hist.sort_by_key(|e| std::cmp::Reverse(e.cursor()));
+			}
+		}
+	};
+
+	if show_settings() {
+		return rsx! {
+			Stylesheet { href: TAILWIND_CSS }
+			div { class: "h-screen w-screen bg-slate-950 text-slate-200 flex flex-col font-sans overflow-hidden rounded-xl border border-slate-800 shadow-2xl",
+				Titlebar { on_settings: move |_| show_settings.set(false) }
+				SettingsView {
+					settings: settings(),
+					error: settings_error(),
+					hotkey_errors: hotkey_errors(),
+					path: settings::settings_path().display().to_string(),
+					on_save: save_settings,
+					on_close: move |_| show_settings.set(false),
+				}
+			}
+		};
+	}
 
 	// splash screen while embedding models load
 	let is_ready = embedder().is_some();
@@ -224,7 +794,7 @@ fn App() -> Element {
 		return rsx! {
 			Stylesheet { href: TAILWIND_CSS }
 			div { class: "h-screen w-screen bg-slate-950 text-slate-200 flex flex-col font-sans overflow-hidden rounded-xl border border-slate-800 shadow-2xl",
-				Titlebar {}
+				Titlebar { on_settings: move |_| show_settings.set(true) }
 				div { class: "flex-1 flex flex-col items-center justify-center gap-4",
 					div { class: "text-4xl font-bold text-slate-700", "shadowpaste" }
 					div { class: "flex items-center gap-3",
@@ -237,62 +807,29 @@ fn App() -> Element {
 		};
 	}
 
-	// item list, don't filter anything out yet (and show similarity)
+	// item list, newest first unless searching (then show similarity)
 	let query = search_query();
-	let items: Vec<(ClipboardEntry, f32)> = {
-		let hist = history();
-		if query.trim().is_empty() {
-			let mut v: Vec<(ClipboardEntry, f32)> = hist.iter().cloned().map(|e| (e, 0.0_f32)).collect();
-			v.reverse(); // most recent first
-			v
-		} else {
-			let query_lower = query.trim().to_lowercase();
-			let q_emb_opt: Option<Vec<f32>> = query_embedding().flatten();
-
-			// (score_for_sorting, entry, similarity)
-			let mut scored: Vec<(f32, ClipboardEntry, f32)> = Vec::new();
-
-			for e in hist.iter() {
-				let text_match = match &e.content {
-					ClipboardContent::Text(t) => t.to_lowercase().contains(&query_lower),
-					_ => false,
-				};
-
-				let emb_sim = if let Some(ref q_emb) = q_emb_opt {
-					e.embedding.as_ref().map(|emb| Embedder::similarity(q_emb, emb)).unwrap_or(0.0)
-				} else {
-					0.0
-				};
-
-				let is_image = matches!(e.content, ClipboardContent::Image(_));
-
-				// it seems like image similarity is about 10x less than text similarity?? even with the same model
-				let emb_sim_for_score = if is_image {
-					emb_sim * 10.0
-				} else {
-					emb_sim
-				};
-
-				// prefer actual text matches, then similarity
-				let score = if text_match {
-					2.0 + emb_sim_for_score
-				} else {
-					emb_sim_for_score
-				};
-
-				scored.push((score, e.clone(), emb_sim));
-			}
+	let searching = !query.trim().is_empty();
+	let pinned_items: Vec<ClipboardEntry> = if searching { Vec::new() } else { pinned() };
+	let items: Vec<(ClipboardEntry, f32, Option<String>)> = if searching {
+		search_results()
+			.unwrap_or_default()
+			.into_iter()
+			.filter(|(e, _, _)| show_primary() || e.source != CaptureSource::Primary)
+			.collect()
+	} else {
+		history().into_iter().map(|e| (e, 0.0_f32, None)).collect()
+	};
 
-			scored.sort_by(|a, b| {
-				b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal)
-			});
+	// highlight what's searched for, not the app filter
+	let query_for_view = db::split_app_filter(&query).1;
 
-			scored.into_iter().map(|(_, entry, sim)| (entry, sim)).collect()
-		}
+	// shown in a banner while capture is paused
+	let paused_until = match capture_state() {
+		CaptureState::Paused { until } => Some(until.map(|until| format!(" until {}", until.format("%H:%M"))).unwrap_or_default()),
+		CaptureState::Recording => None,
 	};
 
-	let query_for_view = query.clone();
-
 	rsx! {
 		Stylesheet { href: TAILWIND_CSS }
 		style { "
@@ -303,7 +840,7 @@ fn App() -> Element {
 		" }
 
 		div { class: "h-screen w-screen bg-slate-950 text-slate-200 flex flex-col font-sans overflow-hidden rounded-xl border border-slate-800 shadow-2xl",
-			Titlebar {}
+			Titlebar { on_settings: move |_| show_settings.set(true) }
 
 			div { class: "flex-1 flex flex-col p-4 gap-4 overflow-hidden",
                 // search bar
@@ -327,15 +864,103 @@ fn App() -> Element {
 					}
 				}
 
+				// middle-click selections only exist on linux
+				if cfg!(all(unix, not(target_os = "macos"))) {
+					div { class: "flex items-center gap-4 shrink-0 -mt-2 px-1 text-xs text-slate-400",
+						label { class: "flex items-center gap-1.5 cursor-pointer",
+							input { r#type: "checkbox", checked: record_primary(), onchange: move |_| toggle_record_primary() }
+							"Record selections"
+						}
+						label { class: "flex items-center gap-1.5 cursor-pointer",
+							input { r#type: "checkbox", checked: show_primary(), onchange: move |_| toggle_show_primary() }
+							"Show selections"
+						}
+					}
+				}
+
+				if let Some(until) = paused_until {
+					div { class: "flex items-center justify-between gap-2 shrink-0 -mt-2 px-3 py-1.5 rounded-lg bg-amber-500/10 border border-amber-500/20 text-xs text-amber-300",
+						"Capture paused{until}"
+						button {
+							class: "px-2 py-0.5 rounded hover:bg-amber-500/20 cursor-pointer",
+							onclick: move |_| resume_capture(),
+							"Resume"
+						}
+					}
+				}
+
+				if !hotkey_errors.read().is_empty() {
+					div { class: "flex items-start justify-between gap-2 shrink-0 -mt-2 px-3 py-1.5 rounded-lg bg-red-500/10 border border-red-500/20 text-xs text-red-300",
+						div { class: "flex flex-col gap-0.5",
+							for error in hotkey_errors() {
+								span { "{error}" }
+							}
+						}
+						button {
+							class: "px-2 py-0.5 rounded hover:bg-red-500/20 cursor-pointer shrink-0",
+							onclick: move |_| show_settings.set(true),
+							"Settings"
+						}
+					}
+				}
+
+				if !ignored_patterns.read().is_empty() {
+					div { class: "flex flex-wrap items-center gap-1.5 shrink-0 -mt-2 px-1 text-xs text-slate-500",
+						// copies from unknown apps are still recorded, don't let the list look like it's working
+						if source_apps.peek().identifies_apps() {
+							"Not recording"
+						} else {
+							span { class: "text-amber-300", title: "Wayland doesn't say which app a copy was made in, so copies from these apps are still recorded",
+								"Inactive on Wayland, still recording"
+							}
+						}
+						for pattern in ignored_patterns() {
+							span { key: "{pattern}", class: "flex items-center gap-1 pl-2 pr-1 py-0.5 rounded-full bg-slate-800 text-slate-300",
+								"{pattern}"
+								button {
+									class: "text-slate-500 hover:text-slate-200 px-1 cursor-pointer",
+									title: "Record {pattern} again",
+									onclick: move |_| set_app_ignored(pattern.clone(), false),
+									"×"
+								}
+							}
+						}
+					}
+				}
+
                 // results
 				div { class: "flex-1 overflow-y-auto pr-1 space-y-2",
-					if items.is_empty() {
+					onscroll: move |evt| {
+						let near_end = evt.scroll_top() + f64::from(evt.client_height()) >= f64::from(evt.scroll_height()) - 200.0;
+						if near_end && search_query().trim().is_empty() {
+							load_more_history();
+						}
+					},
+					if items.is_empty() && pinned_items.is_empty() {
 						div { class: "flex flex-col items-center justify-center h-full text-slate-600 gap-2",
 							div { class: "text-4xl opacity-20", "📋" }
 							p { class: "text-sm", "No clipboard history found" }
 						}
 					}
-					for (entry, sim) in items.iter() {
+					if !pinned_items.is_empty() {
+						h2 { class: "text-[10px] uppercase font-semibold tracking-wider text-slate-500 px-1", "Pinned" }
+						for entry in pinned_items.iter() {
+							div { key: "{entry.id}", class: "group/item",
+								ClipboardView {
+									entry: entry.clone(),
+									on_delete: on_delete_request,
+									on_context_menu: on_context_menu_request,
+									search_query: String::new(),
+									similarity: 0.0,
+									snippet: None,
+								}
+							}
+						}
+						if !items.is_empty() {
+							h2 { class: "text-[10px] uppercase font-semibold tracking-wider text-slate-500 px-1 pt-2", "Recent" }
+						}
+					}
+					for (entry, sim, snippet) in items.iter() {
 						div { key: "{entry.id}", class: "group/item",
 							ClipboardView {
 								entry: entry.clone(),
@@ -343,6 +968,7 @@ fn App() -> Element {
 								on_context_menu: on_context_menu_request,
 								search_query: query_for_view.clone(),
 								similarity: *sim,
+								snippet: snippet.clone(),
 							}
 						}
 					}
@@ -351,43 +977,80 @@ fn App() -> Element {
 
 			// right-click context menu
 			if let Some((id, x, y)) = context_menu() {
-				div {
-					class: "fixed inset-0 z-[100]",
-					onclick: move |_| context_menu.set(None),
-					oncontextmenu: move |evt| {
-						evt.prevent_default();
-						context_menu.set(None);
-					},
-					div {
-						class: "absolute bg-slate-900 border border-slate-700 rounded-md shadow-xl py-1 min-w-[140px]",
-						style: "top: {y}px; left: {x}px;",
-						onclick: move |evt| evt.stop_propagation(),
-						button {
-							class: "w-full px-3 py-1.5 text-left text-sm text-slate-200 hover:bg-slate-800 transition-colors",
-							onclick: move |_| {
-								let entries = history();
-								if let Some(entry) = entries.iter().find(|e| e.id == id) {
-									if let Ok(mut suppressed) = clipboard_write_suppression().lock() {
-										*suppressed = true;
+				{
+					let is_pinned = pinned.read().iter().any(|e| e.id == id);
+					let (source_app, transforms) = context_entry().unwrap_or_default();
+					rsx! {
+						div {
+							class: "fixed inset-0 z-[100]",
+							onclick: move |_| context_menu.set(None),
+							oncontextmenu: move |evt| {
+								evt.prevent_default();
+								context_menu.set(None);
+							},
+							div {
+								class: "absolute bg-slate-900 border border-slate-700 rounded-md shadow-xl py-1 min-w-[140px]",
+								style: "top: {y}px; left: {x}px;",
+								onclick: move |evt| evt.stop_propagation(),
+								if copy_as() {
+									div { class: "max-h-[60vh] overflow-y-auto",
+										for transform in transforms {
+											button {
+												key: "{transform.name}",
+												class: "w-full px-3 py-1.5 text-left text-sm text-slate-200 hover:bg-slate-800 transition-colors",
+												onclick: move |_| {
+													copy_entry(id, Some(transform));
+													context_menu.set(None);
+												},
+												"{transform.label}"
+											}
+										}
+									}
+								} else {
+									button {
+										class: "w-full px-3 py-1.5 text-left text-sm text-slate-200 hover:bg-slate-800 transition-colors",
+										onclick: move |_| {
+											copy_entry(id, None);
+											context_menu.set(None);
+										},
+										"Copy"
+									}
+									if !transforms.is_empty() {
+										button {
+											class: "w-full px-3 py-1.5 text-left text-sm text-slate-200 hover:bg-slate-800 transition-colors",
+											onclick: move |_| copy_as.set(true),
+											"Copy as..."
+										}
+									}
+									button {
+										class: "w-full px-3 py-1.5 text-left text-sm text-slate-200 hover:bg-slate-800 transition-colors",
+										onclick: move |_| {
+											set_pinned(id, !is_pinned);
+											context_menu.set(None);
+										},
+										if is_pinned { "Unpin" } else { "Pin" }
 									}
-									if let Err(err) = write_clipboard_content(&entry.content) {
-										if let Ok(mut suppressed) = clipboard_write_suppression().lock() {
-											*suppressed = false;
+									if let Some(app) = source_app {
+										button {
+											class: "w-full px-3 py-1.5 text-left text-sm text-slate-200 hover:bg-slate-800 transition-colors",
+											title: "Stop recording copies made in {app.name}",
+											onclick: move |_| {
+												set_app_ignored(app.name.clone(), true);
+												context_menu.set(None);
+											},
+											"Ignore {app.name}"
 										}
-										eprintln!("Failed to copy entry: {err}");
+									}
+									button {
+										class: "w-full px-3 py-1.5 text-left text-sm text-red-400 hover:bg-slate-800 transition-colors",
+										onclick: move |_| {
+											context_menu.set(None);
+											pending_delete.set(Some(id));
+										},
+										"Delete"
 									}
 								}
-								context_menu.set(None);
-							},
-							"Copy"
-						}
-						button {
-							class: "w-full px-3 py-1.5 text-left text-sm text-red-400 hover:bg-slate-800 transition-colors",
-							onclick: move |_| {
-								context_menu.set(None);
-								pending_delete.set(Some(id));
-							},
-							"Delete"
+							}
 						}
 					}
 				}
@@ -416,6 +1079,7 @@ fn App() -> Element {
 										let _ = db_guard.delete_by_id(id);
 									}
 									history.write().retain(|e| e.id != id);
+									pinned.write().retain(|e| e.id != id);
 									pending_delete.set(None);
 								},
 								"Delete"
@@ -424,6 +1088,43 @@ fn App() -> Element {
 					}
 				}
 			}
+
+			// clear history confirmation, asked for from the tray
+			if pending_clear() {
+				div {
+					class: "fixed inset-0 z-[110] flex items-center justify-center bg-black/60 backdrop-blur-sm",
+					onclick: move |_| pending_clear.set(false),
+					div {
+						class: "bg-slate-900 border border-slate-700 rounded-lg shadow-2xl p-5 w-80",
+						onclick: move |evt| evt.stop_propagation(),
+						h3 { class: "text-base font-semibold text-slate-200 mb-1", "Clear history?" }
+						p { class: "text-sm text-slate-400 mb-4", "Everything except pinned entries is deleted. This action cannot be undone." }
+						div { class: "flex gap-2 justify-end",
+							button {
+								class: "px-3 py-1.5 text-sm text-slate-300 bg-slate-800 hover:bg-slate-700 rounded-md transition-colors",
+								onclick: move |_| pending_clear.set(false),
+								"Cancel"
+							}
+							button {
+								class: "px-3 py-1.5 text-sm text-white bg-red-500/80 hover:bg-red-500 rounded-md transition-colors",
+								onclick: move |_| {
+									let cleared = db().lock().ok().map(|db_guard| db_guard.clear_history());
+									match cleared {
+										Some(Ok(_)) => {
+											history.write().clear();
+											has_more_history.set(false);
+										}
+										Some(Err(err)) => eprintln!("Failed to clear history: {err}"),
+										None => {}
+									}
+									pending_clear.set(false);
+								},
+								"Clear"
+							}
+						}
+					}
+				}
+			}
 		}
 	}
 }
\ No newline at end of file
@@ -0,0 +1,749 @@
+use anyhow::Context;
+use rusqlite::{Connection, OptionalExtension, Transaction, params};
+use base64::{Engine as _, engine::general_purpose};
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+
+/// a single schema upgrade, runs inside its own transaction
+type Migration = fn(&Transaction) -> rusqlite::Result<()>;
+
+/// ordered schema migrations, entry `i` upgrades the database to version `i + 1`
+/// released migrations must never be edited or reordered, only appended to
+const MIGRATIONS: &[Migration] = &[
+	create_clipboard_history,
+	add_content_hash,
+	add_full_text_index,
+	add_copied_at_index,
+	store_images_as_blobs,
+	add_pinned,
+	add_expires_at,
+	add_source,
+	add_rich_content,
+	add_source_app,
+	add_ignored_apps,
+	hash_links_as_text,
+];
+
+/// schema version this build writes
+pub const LATEST_VERSION: i64 = MIGRATIONS.len() as i64;
+
+/// read the schema version stored in the database header
+pub fn schema_version(conn: &Connection) -> rusqlite::Result<i64> {
+	conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// bring the database up to `LATEST_VERSION`, refusing databases written by a newer build
+pub fn migrate(conn: &mut Connection) -> anyhow::Result<()> {
+	let current = schema_version(conn)?;
+	if current > LATEST_VERSION {
+		anyhow::bail!(
+			"database schema version {current} is newer than this build supports ({LATEST_VERSION}), please update shadowpaste"
+		);
+	}
+
+	for (index, migration) in MIGRATIONS.iter().enumerate().skip(current as usize) {
+		let version = index as i64 + 1;
+		let tx = conn.transaction()?;
+		migration(&tx).with_context(|| format!("migration to schema version {version} failed"))?;
+		tx.pragma_update(None, "user_version", version)?;
+		tx.commit()?;
+	}
+
+	Ok(())
+}
+
+/// v1: the original table, databases from before versioning already have it at version 0
+fn create_clipboard_history(tx: &Transaction) -> rusqlite::Result<()> {
+	tx.execute_batch(
+		"CREATE TABLE IF NOT EXISTS clipboard_history (
+			id           INTEGER PRIMARY KEY AUTOINCREMENT,
+			content_type TEXT    NOT NULL,
+			content      TEXT    NOT NULL,
+			copied_at    TEXT    NOT NULL,
+			embedding    BLOB    NOT NULL
+		);"
+	)
+}
+
+/// v2: content hash for cross-session dedup plus a use counter, existing duplicates are merged into the newest row
+fn add_content_hash(tx: &Transaction) -> rusqlite::Result<()> {
+	tx.execute_batch(
+		"ALTER TABLE clipboard_history ADD COLUMN content_hash TEXT;
+		ALTER TABLE clipboard_history ADD COLUMN use_count INTEGER NOT NULL DEFAULT 1;"
+	)?;
+
+	let rows = {
+		let mut statement = tx.prepare("SELECT id, content_type, content FROM clipboard_history")?;
+		let rows = statement.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?;
+		rows.collect::<Result<Vec<_>, _>>()?
+	};
+	for (id, content_type, content) in rows {
+		tx.execute(
+			"UPDATE clipboard_history SET content_hash = ?1 WHERE id = ?2",
+			params![hash_v2(&content_type, content.as_bytes()), id],
+		)?;
+	}
+
+	tx.execute_batch(
+		"UPDATE clipboard_history SET use_count = (
+			SELECT COUNT(*) FROM clipboard_history AS dup WHERE dup.content_hash = clipboard_history.content_hash
+		);
+		DELETE FROM clipboard_history WHERE id NOT IN (
+			SELECT MAX(id) FROM clipboard_history GROUP BY content_hash
+		);
+		CREATE UNIQUE INDEX idx_clipboard_history_content_hash ON clipboard_history (content_hash);"
+	)
+}
+
+/// the content hash as v2 and v5 wrote it, frozen here so later changes to `db::hash_parts` don't touch old migrations
+fn hash_v2(content_type: &str, payload: &[u8]) -> String {
+	let mut hasher = Sha256::new();
+	hasher.update(content_type.as_bytes());
+	hasher.update([0]);
+	hasher.update(payload);
+	hasher.finalize().iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// v3: fts5 keyword index over text entries, rowid is the clipboard_history id
+fn add_full_text_index(tx: &Transaction) -> rusqlite::Result<()> {
+	tx.execute_batch(
+		"CREATE VIRTUAL TABLE clipboard_fts USING fts5 (content, tokenize = 'unicode61 remove_diacritics 2');
+		INSERT INTO clipboard_fts (rowid, content) SELECT id, content FROM clipboard_history WHERE content_type = 'text';"
+	)
+}
+
+/// v4: index backing the newest-first history pages
+fn add_copied_at_index(tx: &Transaction) -> rusqlite::Result<()> {
+	tx.execute_batch(
+		"CREATE INDEX idx_clipboard_history_copied_at ON clipboard_history (copied_at DESC, id DESC);"
+	)
+}
+
+/// longest side of the list thumbnails v5 generated
+const THUMBNAIL_SIZE_V5: u32 = 512;
+
+/// v5: images move from base64 data uris in `content` to raw png bytes with size metadata and a list thumbnail
+fn store_images_as_blobs(tx: &Transaction) -> rusqlite::Result<()> {
+	tx.execute_batch(
+		"ALTER TABLE clipboard_history ADD COLUMN image_data BLOB;
+		ALTER TABLE clipboard_history ADD COLUMN image_width INTEGER;
+		ALTER TABLE clipboard_history ADD COLUMN image_height INTEGER;
+		ALTER TABLE clipboard_history ADD COLUMN image_size INTEGER;
+		ALTER TABLE clipboard_history ADD COLUMN thumbnail BLOB;"
+	)?;
+
+	let rows = {
+		let mut statement = tx.prepare("SELECT id, content FROM clipboard_history WHERE content_type = 'image'")?;
+		let rows = statement.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+		rows.collect::<Result<Vec<_>, _>>()?
+	};
+	for (id, data_uri) in rows {
+		let png = data_uri.split_once(";base64,")
+			.and_then(|(_, b64)| general_purpose::STANDARD.decode(b64).ok())
+			.filter(|png| !png.is_empty());
+		// nothing left to show, and empty payloads would all get the same hash and trip the unique index
+		let Some(png) = png else {
+			tx.execute("DELETE FROM clipboard_history WHERE id = ?1", params![id])?;
+			continue;
+		};
+		// size, thumbnail and hash as v5 made them, independent of how the app handles images now
+		let (width, height) = image::ImageReader::new(Cursor::new(&png))
+			.with_guessed_format()
+			.ok()
+			.and_then(|reader| reader.into_dimensions().ok())
+			.unwrap_or((0, 0));
+		let thumbnail = image::load_from_memory(&png).ok().and_then(|decoded| {
+			let thumb = if decoded.width() > THUMBNAIL_SIZE_V5 || decoded.height() > THUMBNAIL_SIZE_V5 {
+				decoded.thumbnail(THUMBNAIL_SIZE_V5, THUMBNAIL_SIZE_V5)
+			} else {
+				decoded
+			};
+			let mut cursor = Cursor::new(Vec::new());
+			thumb.write_to(&mut cursor, image::ImageFormat::Png).ok()?;
+			Some(cursor.into_inner())
+		});
+
+		tx.execute(
+			"UPDATE clipboard_history
+				SET content = '', image_data = ?1, image_width = ?2, image_height = ?3, image_size = ?4, thumbnail = ?5, content_hash = ?6
+				WHERE id = ?7",
+			params![png, width, height, png.len() as i64, thumbnail, hash_v2("image", &png), id],
+		)?;
+	}
+
+	Ok(())
+}
+
+/// v6: pinned entries
+fn add_pinned(tx: &Transaction) -> rusqlite::Result<()> {
+	tx.execute_batch(
+		"ALTER TABLE clipboard_history ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0;"
+	)
+}
+
+/// v7: expiry for flagged sensitive entries
+fn add_expires_at(tx: &Transaction) -> rusqlite::Result<()> {
+	tx.execute_batch(
+		"ALTER TABLE clipboard_history ADD COLUMN expires_at TEXT;
+		CREATE INDEX idx_clipboard_history_expires_at ON clipboard_history (expires_at) WHERE expires_at IS NOT NULL;"
+	)
+}
+
+/// v8: which selection an entry came from, `clipboard` or `primary`
+fn add_source(tx: &Transaction) -> rusqlite::Result<()> {
+	tx.execute_batch(
+		"ALTER TABLE clipboard_history ADD COLUMN source TEXT NOT NULL DEFAULT 'clipboard';"
+	)
+}
+
+/// v9: html/rtf markup for formatted entries, `content` keeps their plain text
+fn add_rich_content(tx: &Transaction) -> rusqlite::Result<()> {
+	tx.execute_batch(
+		"ALTER TABLE clipboard_history ADD COLUMN rich_content TEXT;"
+	)
+}
+
+/// v10: the application and window title a copy was made in
+fn add_source_app(tx: &Transaction) -> rusqlite::Result<()> {
+	tx.execute_batch(
+		"ALTER TABLE clipboard_history ADD COLUMN source_app TEXT;
+		ALTER TABLE clipboard_history ADD COLUMN window_title TEXT;"
+	)
+}
+
+/// v11: apps whose copies aren't recorded, as globs over the process name or window class
+fn add_ignored_apps(tx: &Transaction) -> rusqlite::Result<()> {
+	tx.execute_batch(
+		"CREATE TABLE ignored_apps (pattern TEXT PRIMARY KEY COLLATE NOCASE);"
+	)
+}
+
+/// v12: links hash like their text, a link also copied as plain text (selected, or from before links were recognised) is merged into the newer row
+fn hash_links_as_text(tx: &Transaction) -> rusqlite::Result<()> {
+	let rows = {
+		let mut statement = tx.prepare("SELECT id, content, copied_at FROM clipboard_history WHERE content_type = 'url'")?;
+		let rows = statement.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?;
+		rows.collect::<Result<Vec<_>, _>>()?
+	};
+	for (id, url, copied_at) in rows {
+		let hash = hash_v2("text", url.as_bytes());
+		let duplicate: Option<(i64, String)> = tx.query_row(
+			"SELECT id, copied_at FROM clipboard_history WHERE content_hash = ?1",
+			params![hash],
+			|row| Ok((row.get(0)?, row.get(1)?)),
+		).optional()?;
+		let keep = match duplicate {
+			Some((other, other_copied_at)) => {
+				let (keep, merged) = if copied_at >= other_copied_at { (id, other) } else { (other, id) };
+				tx.execute(
+					"UPDATE clipboard_history SET
+							use_count = use_count + (SELECT use_count FROM clipboard_history WHERE id = ?2),
+							pinned = MAX(pinned, (SELECT pinned FROM clipboard_history WHERE id = ?2))
+						WHERE id = ?1",
+					params![keep, merged],
+				)?;
+				tx.execute("DELETE FROM clipboard_history WHERE id = ?1", params![merged])?;
+				tx.execute("DELETE FROM clipboard_fts WHERE rowid = ?1", params![merged])?;
+				keep
+			}
+			None => id,
+		};
+		tx.execute("UPDATE clipboard_history SET content_hash = ?1 WHERE id = ?2", params![hash, keep])?;
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::db::{self, Database};
+	use chrono::Local;
+	use crate::monitor::{CaptureSource, ClipboardContent, ClipboardImage};
+
+	/// snapshot of the schema and some rows as each released version wrote them, index is the version
+	/// append a fixture whenever a migration is added
+	const FIXTURES: &[&str] = &[
+		// v0: created by `Database::open` before migrations existed
+		"CREATE TABLE clipboard_history (
+			id           INTEGER PRIMARY KEY AUTOINCREMENT,
+			content_type TEXT    NOT NULL,
+			content      TEXT    NOT NULL,
+			copied_at    TEXT    NOT NULL,
+			embedding    BLOB    NOT NULL
+		);
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding)
+			VALUES ('text', 'hello world', '2024-01-01T12:00:00+00:00', x'0000803f00000040');
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding)
+			VALUES ('image', 'data:image/png;base64,iVBORw0KGgo=', '2024-01-02T12:00:00+00:00', x'');",
+		// v1
+		"CREATE TABLE clipboard_history (
+			id           INTEGER PRIMARY KEY AUTOINCREMENT,
+			content_type TEXT    NOT NULL,
+			content      TEXT    NOT NULL,
+			copied_at    TEXT    NOT NULL,
+			embedding    BLOB    NOT NULL
+		);
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding)
+			VALUES ('text', 'hello world', '2024-01-01T12:00:00+00:00', x'0000803f00000040');
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding)
+			VALUES ('image', 'data:image/png;base64,iVBORw0KGgo=', '2024-01-02T12:00:00+00:00', x'');
+		PRAGMA user_version = 1;",
+		// v2
+		"CREATE TABLE clipboard_history (
+			id           INTEGER PRIMARY KEY AUTOINCREMENT,
+			content_type TEXT    NOT NULL,
+			content      TEXT    NOT NULL,
+			copied_at    TEXT    NOT NULL,
+			embedding    BLOB    NOT NULL,
+			content_hash TEXT,
+			use_count    INTEGER NOT NULL DEFAULT 1
+		);
+		CREATE UNIQUE INDEX idx_clipboard_history_content_hash ON clipboard_history (content_hash);
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count)
+			VALUES ('text', 'hello world', '2024-01-01T12:00:00+00:00', x'0000803f00000040',
+				'8f75229b47b43535ce7af30a5a8ea5095d9c83c7dde742b53682b35e71288522', 1);
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count)
+			VALUES ('image', 'data:image/png;base64,iVBORw0KGgo=', '2024-01-02T12:00:00+00:00', x'',
+				'1fe9c123195bf05b7d00184dd1d5f28a513f812bfba441709b8e751ada95b000', 1);
+		PRAGMA user_version = 2;",
+		// v3
+		"CREATE TABLE clipboard_history (
+			id           INTEGER PRIMARY KEY AUTOINCREMENT,
+			content_type TEXT    NOT NULL,
+			content      TEXT    NOT NULL,
+			copied_at    TEXT    NOT NULL,
+			embedding    BLOB    NOT NULL,
+			content_hash TEXT,
+			use_count    INTEGER NOT NULL DEFAULT 1
+		);
+		CREATE UNIQUE INDEX idx_clipboard_history_content_hash ON clipboard_history (content_hash);
+		CREATE VIRTUAL TABLE clipboard_fts USING fts5 (content, tokenize = 'unicode61 remove_diacritics 2');
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count)
+			VALUES ('text', 'hello world', '2024-01-01T12:00:00+00:00', x'0000803f00000040',
+				'8f75229b47b43535ce7af30a5a8ea5095d9c83c7dde742b53682b35e71288522', 1);
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count)
+			VALUES ('image', 'data:image/png;base64,iVBORw0KGgo=', '2024-01-02T12:00:00+00:00', x'',
+				'1fe9c123195bf05b7d00184dd1d5f28a513f812bfba441709b8e751ada95b000', 1);
+		INSERT INTO clipboard_fts (rowid, content) VALUES (1, 'hello world');
+		PRAGMA user_version = 3;",
+		// v4
+		"CREATE TABLE clipboard_history (
+			id           INTEGER PRIMARY KEY AUTOINCREMENT,
+			content_type TEXT    NOT NULL,
+			content      TEXT    NOT NULL,
+			copied_at    TEXT    NOT NULL,
+			embedding    BLOB    NOT NULL,
+			content_hash TEXT,
+			use_count    INTEGER NOT NULL DEFAULT 1
+		);
+		CREATE UNIQUE INDEX idx_clipboard_history_content_hash ON clipboard_history (content_hash);
+		CREATE INDEX idx_clipboard_history_copied_at ON clipboard_history (copied_at DESC, id DESC);
+		CREATE VIRTUAL TABLE clipboard_fts USING fts5 (content, tokenize = 'unicode61 remove_diacritics 2');
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count)
+			VALUES ('text', 'hello world', '2024-01-01T12:00:00+00:00', x'0000803f00000040',
+				'8f75229b47b43535ce7af30a5a8ea5095d9c83c7dde742b53682b35e71288522', 1);
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count)
+			VALUES ('image', 'data:image/png;base64,iVBORw0KGgo=', '2024-01-02T12:00:00+00:00', x'',
+				'1fe9c123195bf05b7d00184dd1d5f28a513f812bfba441709b8e751ada95b000', 1);
+		INSERT INTO clipboard_fts (rowid, content) VALUES (1, 'hello world');
+		PRAGMA user_version = 4;",
+		// v5
+		"CREATE TABLE clipboard_history (
+			id           INTEGER PRIMARY KEY AUTOINCREMENT,
+			content_type TEXT    NOT NULL,
+			content      TEXT    NOT NULL,
+			copied_at    TEXT    NOT NULL,
+			embedding    BLOB    NOT NULL,
+			content_hash TEXT,
+			use_count    INTEGER NOT NULL DEFAULT 1,
+			image_data   BLOB,
+			image_width  INTEGER,
+			image_height INTEGER,
+			image_size   INTEGER,
+			thumbnail    BLOB
+		);
+		CREATE UNIQUE INDEX idx_clipboard_history_content_hash ON clipboard_history (content_hash);
+		CREATE INDEX idx_clipboard_history_copied_at ON clipboard_history (copied_at DESC, id DESC);
+		CREATE VIRTUAL TABLE clipboard_fts USING fts5 (content, tokenize = 'unicode61 remove_diacritics 2');
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count)
+			VALUES ('text', 'hello world', '2024-01-01T12:00:00+00:00', x'0000803f00000040',
+				'8f75229b47b43535ce7af30a5a8ea5095d9c83c7dde742b53682b35e71288522', 1);
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count,
+				image_data, image_width, image_height, image_size, thumbnail)
+			VALUES ('image', '', '2024-01-02T12:00:00+00:00', x'',
+				'ebe43636939835a8ae833e1ee7be3b9202f96ab81238288ca97dc47b8c0081ec', 1,
+				x'89504e470d0a1a0a', 0, 0, 8, NULL);
+		INSERT INTO clipboard_fts (rowid, content) VALUES (1, 'hello world');
+		PRAGMA user_version = 5;",
+		// v6
+		"CREATE TABLE clipboard_history (
+			id           INTEGER PRIMARY KEY AUTOINCREMENT,
+			content_type TEXT    NOT NULL,
+			content      TEXT    NOT NULL,
+			copied_at    TEXT    NOT NULL,
+			embedding    BLOB    NOT NULL,
+			content_hash TEXT,
+			use_count    INTEGER NOT NULL DEFAULT 1,
+			image_data   BLOB,
+			image_width  INTEGER,
+			image_height INTEGER,
+			image_size   INTEGER,
+			thumbnail    BLOB,
+			pinned       INTEGER NOT NULL DEFAULT 0
+		);
+		CREATE UNIQUE INDEX idx_clipboard_history_content_hash ON clipboard_history (content_hash);
+		CREATE INDEX idx_clipboard_history_copied_at ON clipboard_history (copied_at DESC, id DESC);
+		CREATE VIRTUAL TABLE clipboard_fts USING fts5 (content, tokenize = 'unicode61 remove_diacritics 2');
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count)
+			VALUES ('text', 'hello world', '2024-01-01T12:00:00+00:00', x'0000803f00000040',
+				'8f75229b47b43535ce7af30a5a8ea5095d9c83c7dde742b53682b35e71288522', 1);
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count,
+				image_data, image_width, image_height, image_size, thumbnail)
+			VALUES ('image', '', '2024-01-02T12:00:00+00:00', x'',
+				'ebe43636939835a8ae833e1ee7be3b9202f96ab81238288ca97dc47b8c0081ec', 1,
+				x'89504e470d0a1a0a', 0, 0, 8, NULL);
+		INSERT INTO clipboard_fts (rowid, content) VALUES (1, 'hello world');
+		PRAGMA user_version = 6;",
+		// v7
+		"CREATE TABLE clipboard_history (
+			id           INTEGER PRIMARY KEY AUTOINCREMENT,
+			content_type TEXT    NOT NULL,
+			content      TEXT    NOT NULL,
+			copied_at    TEXT    NOT NULL,
+			embedding    BLOB    NOT NULL,
+			content_hash TEXT,
+			use_count    INTEGER NOT NULL DEFAULT 1,
+			image_data   BLOB,
+			image_width  INTEGER,
+			image_height INTEGER,
+			image_size   INTEGER,
+			thumbnail    BLOB,
+			pinned       INTEGER NOT NULL DEFAULT 0,
+			expires_at   TEXT
+		);
+		CREATE UNIQUE INDEX idx_clipboard_history_content_hash ON clipboard_history (content_hash);
+		CREATE INDEX idx_clipboard_history_copied_at ON clipboard_history (copied_at DESC, id DESC);
+		CREATE INDEX idx_clipboard_history_expires_at ON clipboard_history (expires_at) WHERE expires_at IS NOT NULL;
+		CREATE VIRTUAL TABLE clipboard_fts USING fts5 (content, tokenize = 'unicode61 remove_diacritics 2');
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count)
+			VALUES ('text', 'hello world', '2024-01-01T12:00:00+00:00', x'0000803f00000040',
+				'8f75229b47b43535ce7af30a5a8ea5095d9c83c7dde742b53682b35e71288522', 1);
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count,
+				image_data, image_width, image_height, image_size, thumbnail)
+			VALUES ('image', '', '2024-01-02T12:00:00+00:00', x'',
+				'ebe43636939835a8ae833e1ee7be3b9202f96ab81238288ca97dc47b8c0081ec', 1,
+				x'89504e470d0a1a0a', 0, 0, 8, NULL);
+		INSERT INTO clipboard_fts (rowid, content) VALUES (1, 'hello world');
+		PRAGMA user_version = 7;",
+		// v8
+		"CREATE TABLE clipboard_history (
+			id           INTEGER PRIMARY KEY AUTOINCREMENT,
+			content_type TEXT    NOT NULL,
+			content      TEXT    NOT NULL,
+			copied_at    TEXT    NOT NULL,
+			embedding    BLOB    NOT NULL,
+			content_hash TEXT,
+			use_count    INTEGER NOT NULL DEFAULT 1,
+			image_data   BLOB,
+			image_width  INTEGER,
+			image_height INTEGER,
+			image_size   INTEGER,
+			thumbnail    BLOB,
+			pinned       INTEGER NOT NULL DEFAULT 0,
+			expires_at   TEXT,
+			source       TEXT    NOT NULL DEFAULT 'clipboard'
+		);
+		CREATE UNIQUE INDEX idx_clipboard_history_content_hash ON clipboard_history (content_hash);
+		CREATE INDEX idx_clipboard_history_copied_at ON clipboard_history (copied_at DESC, id DESC);
+		CREATE INDEX idx_clipboard_history_expires_at ON clipboard_history (expires_at) WHERE expires_at IS NOT NULL;
+		CREATE VIRTUAL TABLE clipboard_fts USING fts5 (content, tokenize = 'unicode61 remove_diacritics 2');
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count)
+			VALUES ('text', 'hello world', '2024-01-01T12:00:00+00:00', x'0000803f00000040',
+				'8f75229b47b43535ce7af30a5a8ea5095d9c83c7dde742b53682b35e71288522', 1);
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count,
+				image_data, image_width, image_height, image_size, thumbnail)
+			VALUES ('image', '', '2024-01-02T12:00:00+00:00', x'',
+				'ebe43636939835a8ae833e1ee7be3b9202f96ab81238288ca97dc47b8c0081ec', 1,
+				x'89504e470d0a1a0a', 0, 0, 8, NULL);
+		INSERT INTO clipboard_fts (rowid, content) VALUES (1, 'hello world');
+		PRAGMA user_version = 8;",
+		// v9
+		"CREATE TABLE clipboard_history (
+			id           INTEGER PRIMARY KEY AUTOINCREMENT,
+			content_type TEXT    NOT NULL,
+			content      TEXT    NOT NULL,
+			copied_at    TEXT    NOT NULL,
+			embedding    BLOB    NOT NULL,
+			content_hash TEXT,
+			use_count    INTEGER NOT NULL DEFAULT 1,
+			image_data   BLOB,
+			image_width  INTEGER,
+			image_height INTEGER,
+			image_size   INTEGER,
+			thumbnail    BLOB,
+			pinned       INTEGER NOT NULL DEFAULT 0,
+			expires_at   TEXT,
+			source       TEXT    NOT NULL DEFAULT 'clipboard',
+			rich_content TEXT
+		);
+		CREATE UNIQUE INDEX idx_clipboard_history_content_hash ON clipboard_history (content_hash);
+		CREATE INDEX idx_clipboard_history_copied_at ON clipboard_history (copied_at DESC, id DESC);
+		CREATE INDEX idx_clipboard_history_expires_at ON clipboard_history (expires_at) WHERE expires_at IS NOT NULL;
+		CREATE VIRTUAL TABLE clipboard_fts USING fts5 (content, tokenize = 'unicode61 remove_diacritics 2');
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count)
+			VALUES ('text', 'hello world', '2024-01-01T12:00:00+00:00', x'0000803f00000040',
+				'8f75229b47b43535ce7af30a5a8ea5095d9c83c7dde742b53682b35e71288522', 1);
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count,
+				image_data, image_width, image_height, image_size, thumbnail)
+			VALUES ('image', '', '2024-01-02T12:00:00+00:00', x'',
+				'ebe43636939835a8ae833e1ee7be3b9202f96ab81238288ca97dc47b8c0081ec', 1,
+				x'89504e470d0a1a0a', 0, 0, 8, NULL);
+		INSERT INTO clipboard_fts (rowid, content) VALUES (1, 'hello world');
+		PRAGMA user_version = 9;",
+		// v10
+		"CREATE TABLE clipboard_history (
+			id           INTEGER PRIMARY KEY AUTOINCREMENT,
+			content_type TEXT    NOT NULL,
+			content      TEXT    NOT NULL,
+			copied_at    TEXT    NOT NULL,
+			embedding    BLOB    NOT NULL,
+			content_hash TEXT,
+			use_count    INTEGER NOT NULL DEFAULT 1,
+			image_data   BLOB,
+			image_width  INTEGER,
+			image_height INTEGER,
+			image_size   INTEGER,
+			thumbnail    BLOB,
+			pinned       INTEGER NOT NULL DEFAULT 0,
+			expires_at   TEXT,
+			source       TEXT    NOT NULL DEFAULT 'clipboard',
+			rich_content TEXT,
+			source_app   TEXT,
+			window_title TEXT
+		);
+		CREATE UNIQUE INDEX idx_clipboard_history_content_hash ON clipboard_history (content_hash);
+		CREATE INDEX idx_clipboard_history_copied_at ON clipboard_history (copied_at DESC, id DESC);
+		CREATE INDEX idx_clipboard_history_expires_at ON clipboard_history (expires_at) WHERE expires_at IS NOT NULL;
+		CREATE VIRTUAL TABLE clipboard_fts USING fts5 (content, tokenize = 'unicode61 remove_diacritics 2');
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count)
+			VALUES ('text', 'hello world', '2024-01-01T12:00:00+00:00', x'0000803f00000040',
+				'8f75229b47b43535ce7af30a5a8ea5095d9c83c7dde742b53682b35e71288522', 1);
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count,
+				image_data, image_width, image_height, image_size, thumbnail)
+			VALUES ('image', '', '2024-01-02T12:00:00+00:00', x'',
+				'ebe43636939835a8ae833e1ee7be3b9202f96ab81238288ca97dc47b8c0081ec', 1,
+				x'89504e470d0a1a0a', 0, 0, 8, NULL);
+		INSERT INTO clipboard_fts (rowid, content) VALUES (1, 'hello world');
+		PRAGMA user_version = 10;",
+		// v11
+		"CREATE TABLE clipboard_history (
+			id           INTEGER PRIMARY KEY AUTOINCREMENT,
+			content_type TEXT    NOT NULL,
+			content      TEXT    NOT NULL,
+			copied_at    TEXT    NOT NULL,
+			embedding    BLOB    NOT NULL,
+			content_hash TEXT,
+			use_count    INTEGER NOT NULL DEFAULT 1,
+			image_data   BLOB,
+			image_width  INTEGER,
+			image_height INTEGER,
+			image_size   INTEGER,
+			thumbnail    BLOB,
+			pinned       INTEGER NOT NULL DEFAULT 0,
+			expires_at   TEXT,
+			source       TEXT    NOT NULL DEFAULT 'clipboard',
+			rich_content TEXT,
+			source_app   TEXT,
+			window_title TEXT
+		);
+		CREATE UNIQUE INDEX idx_clipboard_history_content_hash ON clipboard_history (content_hash);
+		CREATE INDEX idx_clipboard_history_copied_at ON clipboard_history (copied_at DESC, id DESC);
+		CREATE INDEX idx_clipboard_history_expires_at ON clipboard_history (expires_at) WHERE expires_at IS NOT NULL;
+		CREATE VIRTUAL TABLE clipboard_fts USING fts5 (content, tokenize = 'unicode61 remove_diacritics 2');
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count)
+			VALUES ('text', 'hello world', '2024-01-01T12:00:00+00:00', x'0000803f00000040',
+				'8f75229b47b43535ce7af30a5a8ea5095d9c83c7dde742b53682b35e71288522', 1);
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count,
+				image_data, image_width, image_height, image_size, thumbnail)
+			VALUES ('image', '', '2024-01-02T12:00:00+00:00', x'',
+				'ebe43636939835a8ae833e1ee7be3b9202f96ab81238288ca97dc47b8c0081ec', 1,
+				x'89504e470d0a1a0a', 0, 0, 8, NULL);
+		INSERT INTO clipboard_fts (rowid, content) VALUES (1, 'hello world');
+		CREATE TABLE ignored_apps (pattern TEXT PRIMARY KEY COLLATE NOCASE);
+		PRAGMA user_version = 11;",
+		// v12
+		"CREATE TABLE clipboard_history (
+			id           INTEGER PRIMARY KEY AUTOINCREMENT,
+			content_type TEXT    NOT NULL,
+			content      TEXT    NOT NULL,
+			copied_at    TEXT    NOT NULL,
+			embedding    BLOB    NOT NULL,
+			content_hash TEXT,
+			use_count    INTEGER NOT NULL DEFAULT 1,
+			image_data   BLOB,
+			image_width  INTEGER,
+			image_height INTEGER,
+			image_size   INTEGER,
+			thumbnail    BLOB,
+			pinned       INTEGER NOT NULL DEFAULT 0,
+			expires_at   TEXT,
+			source       TEXT    NOT NULL DEFAULT 'clipboard',
+			rich_content TEXT,
+			source_app   TEXT,
+			window_title TEXT
+		);
+		CREATE UNIQUE INDEX idx_clipboard_history_content_hash ON clipboard_history (content_hash);
+		CREATE INDEX idx_clipboard_history_copied_at ON clipboard_history (copied_at DESC, id DESC);
+		CREATE INDEX idx_clipboard_history_expires_at ON clipboard_history (expires_at) WHERE expires_at IS NOT NULL;
+		CREATE VIRTUAL TABLE clipboard_fts USING fts5 (content, tokenize = 'unicode61 remove_diacritics 2');
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count)
+			VALUES ('text', 'hello world', '2024-01-01T12:00:00+00:00', x'0000803f00000040',
+				'8f75229b47b43535ce7af30a5a8ea5095d9c83c7dde742b53682b35e71288522', 1);
+		INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count,
+				image_data, image_width, image_height, image_size, thumbnail)
+			VALUES ('image', '', '2024-01-02T12:00:00+00:00', x'',
+				'ebe43636939835a8ae833e1ee7be3b9202f96ab81238288ca97dc47b8c0081ec', 1,
+				x'89504e470d0a1a0a', 0, 0, 8, NULL);
+		INSERT INTO clipboard_fts (rowid, content) VALUES (1, 'hello world');
+		CREATE TABLE ignored_apps (pattern TEXT PRIMARY KEY COLLATE NOCASE);
+		PRAGMA user_version = 12;",
+	];
+
+	fn load_fixture(version: usize) -> Connection {
+		let conn = Connection::open_in_memory().unwrap();
+		conn.execute_batch(FIXTURES[version]).unwrap();
+		conn
+	}
+
+	#[test]
+	fn every_version_has_a_fixture() {
+		assert_eq!(FIXTURES.len() as i64, LATEST_VERSION + 1);
+	}
+
+	#[test]
+	fn upgrades_every_past_version() {
+		for version in 0..FIXTURES.len() {
+			let conn = load_fixture(version);
+			assert_eq!(schema_version(&conn).unwrap(), version as i64);
+
+			let db = Database::from_connection(conn).unwrap_or_else(|e| panic!("upgrade from v{version}: {e:#}"));
+			let page = db.load_page(None, 10, true).unwrap();
+
+			// newest first
+			assert_eq!(page.len(), 2, "rows lost upgrading from v{version}");
+			assert!(matches!(page[0].content, ClipboardContent::Image(_)));
+			assert_eq!(page[1].content, ClipboardContent::Text("hello world".to_string()));
+
+			let text = db.load_entry(page[1].id).unwrap().unwrap();
+			assert_eq!(text.embedding, Some(vec![1.0, 2.0]));
+
+			// the fixtures only carry the png signature, it should survive as raw bytes
+			let image = db.load_entry(page[0].id).unwrap().unwrap();
+			assert_eq!(image.content, ClipboardContent::Image(ClipboardImage::from_png(vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'])));
+
+			let hits = db.search_text("hel", 10).unwrap();
+			assert_eq!(hits.iter().map(|m| m.id).collect::<Vec<_>>(), vec![text.id], "keyword index missing after upgrading from v{version}");
+		}
+	}
+
+	#[test]
+	fn merges_duplicates_when_adding_content_hash() {
+		let conn = load_fixture(1);
+		conn.execute_batch(
+			"INSERT INTO clipboard_history (content_type, content, copied_at, embedding)
+				VALUES ('text', 'hello world', '2024-01-03T12:00:00+00:00', x'0000803f00000040');"
+		).unwrap();
+
+		let db = Database::from_connection(conn).unwrap();
+		let entries = db.load_page(None, 10, true).unwrap();
+
+		assert_eq!(entries.len(), 2);
+		let hello = entries.iter().find(|e| e.content == ClipboardContent::Text("hello world".to_string())).unwrap();
+		assert_eq!(hello.id, 3);
+		assert_eq!(hello.use_count, 2);
+	}
+
+	#[test]
+	fn converts_base64_images_to_blobs() {
+		let mut png = Vec::new();
+		image::RgbaImage::new(3, 2).write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+		let data_uri = format!("data:image/png;base64,{}", general_purpose::STANDARD.encode(&png));
+
+		let conn = load_fixture(4);
+		conn.execute(
+			"INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count)
+				VALUES ('image', ?1, '2024-01-03T12:00:00+00:00', x'', 'legacy', 1)",
+			params![data_uri],
+		).unwrap();
+		let id = conn.last_insert_rowid();
+
+		let db = Database::from_connection(conn).unwrap();
+		let entry = db.load_entry(id).unwrap().unwrap();
+
+		assert_eq!(entry.content, ClipboardContent::Image(ClipboardImage { png: png.clone(), width: 3, height: 2, byte_size: png.len() }));
+		assert_eq!(db::content_hash(&entry.content), db::hash_parts("image", &png));
+		assert!(db.load_thumbnail(id).unwrap().is_some());
+	}
+
+	#[test]
+	fn drops_images_that_dont_decode() {
+		let conn = load_fixture(4);
+		conn.execute_batch(
+			"INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count)
+				VALUES ('image', 'data:image/png;base64,not base64!', '2024-01-03T12:00:00+00:00', x'', 'corrupt-1', 1);
+			INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count)
+				VALUES ('image', 'not a data uri', '2024-01-04T12:00:00+00:00', x'', 'corrupt-2', 1);"
+		).unwrap();
+
+		let db = Database::from_connection(conn).unwrap();
+		let page = db.load_page(None, 10, true).unwrap();
+
+		assert_eq!(page.iter().map(|e| e.id).collect::<Vec<_>>(), vec![2, 1]);
+	}
+
+	#[test]
+	fn merges_links_with_the_same_text() {
+		let conn = load_fixture(11);
+		// selected as plain text first, then copied as a link
+		conn.execute(
+			"INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count, pinned)
+				VALUES ('text', 'https://example.com', '2024-01-03T12:00:00+00:00', x'', ?1, 2, 1)",
+			params![hash_v2("text", b"https://example.com")],
+		).unwrap();
+		conn.execute_batch(
+			"INSERT INTO clipboard_history (content_type, content, copied_at, embedding, content_hash, use_count)
+				VALUES ('url', 'https://example.com', '2024-01-04T12:00:00+00:00', x'', 'url:https://example.com', 1);
+			INSERT INTO clipboard_fts (rowid, content) VALUES (3, 'https://example.com'), (4, 'https://example.com');"
+		).unwrap();
+
+		let db = Database::from_connection(conn).unwrap();
+
+		// the newer link survives, with the text's uses and pin
+		assert!(db.load_entry(3).unwrap().is_none());
+		let link = db.load_entry(4).unwrap().unwrap();
+		assert_eq!(link.content, ClipboardContent::Url("https://example.com".to_string()));
+		assert_eq!(link.use_count, 3);
+		assert!(link.pinned);
+		assert_eq!(db.search_text("example", 10).unwrap().iter().map(|m| m.id).collect::<Vec<_>>(), vec![4]);
+		assert_eq!(db.touch_existing(&ClipboardContent::Text("https://example.com".to_string()), CaptureSource::Primary, None, Local::now(), None).unwrap().map(|e| e.id), Some(4));
+	}
+
+	#[test]
+	fn fresh_database_is_at_latest_version() {
+		let mut conn = Connection::open_in_memory().unwrap();
+		migrate(&mut conn).unwrap();
+		assert_eq!(schema_version(&conn).unwrap(), LATEST_VERSION);
+
+		// running again is a no-op
+		migrate(&mut conn).unwrap();
+		assert_eq!(schema_version(&conn).unwrap(), LATEST_VERSION);
+	}
+
+	#[test]
+	fn refuses_newer_database() {
+		let mut conn = Connection::open_in_memory().unwrap();
+		conn.pragma_update(None, "user_version", LATEST_VERSION + 1).unwrap();
+		assert!(migrate(&mut conn).is_err());
+	}
+}
@@ -1,19 +1,95 @@
 use arboard::Clipboard;
+use crate::capture_hints::{CaptureDecision, FormatReader, RTF_FORMAT};
+use crate::primary::{self, PrimaryCapture};
+use crate::source_app::{IgnoredApps, SourceApp, SourceAppReader};
+use chrono::{DateTime, Local};
+use std::sync::{Arc, Mutex};
 use clipboard_master::{CallbackResult, ClipboardHandler, Master};
 use std::{thread::{self, sleep}, time};
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use image::{ImageBuffer, Rgba};
-use base64::{Engine as _, engine::general_purpose};
 use std::io::Cursor;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use regex::Regex;
+
+/// longest side of generated list thumbnails, in pixels
+const THUMBNAIL_SIZE: u32 = 512;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipboardImage {
+	/// raw png bytes, empty for entries loaded as part of a history page
+	pub png: Vec<u8>,
+	pub width: u32,
+	pub height: u32,
+	/// size of the png in bytes, kept even when `png` isn't loaded
+	pub byte_size: usize,
+}
+
+impl ClipboardImage {
+	#[cfg(test)]
+	pub fn from_png(png: Vec<u8>) -> Self {
+		let (width, height) = image::ImageReader::new(Cursor::new(&png))
+			.with_guessed_format()
+			.ok()
+			.and_then(|reader| reader.into_dimensions().ok())
+			.unwrap_or((0, 0));
+		let byte_size = png.len();
+		Self { png, width, height, byte_size }
+	}
+
+	/// downscaled png for list rendering, `None` if the image can't be decoded
+	pub fn thumbnail(&self) -> Option<Vec<u8>> {
+		let img = image::load_from_memory(&self.png).ok()?;
+		let thumb = if img.width() > THUMBNAIL_SIZE || img.height() > THUMBNAIL_SIZE {
+			img.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+		} else {
+			img
+		};
+		let mut cursor = Cursor::new(Vec::new());
+		thumb.write_to(&mut cursor, image::ImageFormat::Png).ok()?;
+		Some(cursor.into_inner())
+	}
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ClipboardContent {
 	Text(String),
-	Image(String),
+	Image(ClipboardImage),
+	/// formatted text with its plain text alternative, `html` is empty for entries loaded as part of a history page
+	Html { html: String, text: String },
+	/// rich text with its plain text alternative, `rtf` is empty for entries loaded as part of a history page
+	Rtf { rtf: String, text: String },
+	/// files copied in a file manager
+	Files(Vec<PathBuf>),
+	/// a copied link on its own
+	Url(String),
 	Empty
 }
 
 impl ClipboardContent {
+	/// the text search, embeddings and the sensitive detector work with, `None` for images
+	pub fn plain_text(&self) -> Option<String> {
+		match self {
+			ClipboardContent::Text(text) | ClipboardContent::Url(text) => Some(text.clone()),
+			ClipboardContent::Html { text, .. } | ClipboardContent::Rtf { text, .. } => Some(text.clone()),
+			ClipboardContent::Files(paths) => Some(paths.iter().map(|p| p.to_string_lossy()).collect::<Vec<_>>().join("\n")),
+			ClipboardContent::Image(_) | ClipboardContent::Empty => None,
+		}
+	}
+
+	/// plain text, or a url if that's all it is
+	pub fn from_text(text: String) -> Self {
+		let trimmed = text.trim();
+		let is_url = (trimmed.starts_with("https://") || trimmed.starts_with("http://"))
+			&& !trimmed.contains(char::is_whitespace);
+		if is_url {
+			ClipboardContent::Url(trimmed.to_string())
+		} else {
+			ClipboardContent::Text(text)
+		}
+	}
+
 	pub fn from_image(image: arboard::ImageData) -> Self {
 		let width = image.width;
 		let height = image.height;
@@ -21,10 +97,14 @@ impl ClipboardContent {
 
 		if let Some(img_buffer) = ImageBuffer::<Rgba<u8>, _>::from_raw(width as u32, height as u32, bytes.into_owned()) {
 			let mut cursor = Cursor::new(Vec::new());
-			if let Ok(_) = img_buffer.write_to(&mut cursor, image::ImageFormat::Png) {
-				let base64_string = general_purpose::STANDARD.encode(cursor.into_inner());
-				let final_string = format!("data:image/png;base64,{}", base64_string);
-				return ClipboardContent::Image(final_string);
+			if img_buffer.write_to(&mut cursor, image::ImageFormat::Png).is_ok() {
+				let png = cursor.into_inner();
+				return ClipboardContent::Image(ClipboardImage {
+					byte_size: png.len(),
+					png,
+					width: width as u32,
+					height: height as u32,
+				});
 			}
 		}
 
@@ -32,34 +112,187 @@ impl ClipboardContent {
 	}
 }
 
+/// which selection an entry was captured from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaptureSource {
+	/// the regular clipboard
+	#[default]
+	Clipboard,
+	/// the X11/Wayland PRIMARY selection, set by selecting text and pasted with middle-click
+	Primary,
+}
+
+impl CaptureSource {
+	pub fn as_str(self) -> &'static str {
+		match self {
+			CaptureSource::Clipboard => "clipboard",
+			CaptureSource::Primary => "primary",
+		}
+	}
+
+	pub fn parse(s: &str) -> Self {
+		match s {
+			"primary" => CaptureSource::Primary,
+			_ => CaptureSource::Clipboard,
+		}
+	}
+}
+
+/// a clipboard change picked up by the listener
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capture {
+	pub content: ClipboardContent,
+	/// the source marked it as a password
+	pub concealed: bool,
+	pub source: CaptureSource,
+	/// the application focused at the time, if it could be found out
+	pub app: Option<SourceApp>,
+}
+
+/// whether capture is running, see `CapturePause`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureState {
+	Recording,
+	/// nothing is recorded, until the given time or until resumed
+	Paused { until: Option<DateTime<Local>> },
+}
+
+/// stops the listener from recording anything, optionally resuming by itself
+#[derive(Clone, Default)]
+pub struct CapturePause {
+	state: Arc<Mutex<Option<Option<DateTime<Local>>>>>,
+}
+
+impl CapturePause {
+	/// pause until `until`, or until `resume` if that's `None`
+	pub fn pause(&self, until: Option<DateTime<Local>>) {
+		if let Ok(mut state) = self.state.lock() {
+			*state = Some(until);
+		}
+	}
+
+	pub fn resume(&self) {
+		if let Ok(mut state) = self.state.lock() {
+			*state = None;
+		}
+	}
+
+	/// the state at `now`, a pause whose time has come is over
+	pub fn state_at(&self, now: DateTime<Local>) -> CaptureState {
+		let Ok(mut state) = self.state.lock() else {
+			return CaptureState::Recording;
+		};
+		match *state {
+			Some(Some(until)) if until <= now => {
+				*state = None;
+				CaptureState::Recording
+			}
+			Some(until) => CaptureState::Paused { until },
+			None => CaptureState::Recording,
+		}
+	}
+
+	pub fn state(&self) -> CaptureState {
+		self.state_at(Local::now())
+	}
+
+	pub fn is_paused(&self) -> bool {
+		self.state() != CaptureState::Recording
+	}
+}
+
+/// switches shared with the listener threads, changes apply from the next copy on
+#[derive(Clone, Default)]
+pub struct CaptureControls {
+	/// record the PRIMARY selection too
+	pub primary: PrimaryCapture,
+	/// copies made in apps matching these are never sent on
+	pub ignored: IgnoredApps,
+	pub pause: CapturePause,
+}
+
+impl CaptureControls {
+	/// whether the copy was made in an app on the ignore list
+	pub fn is_ignored(&self, app: Option<&SourceApp>) -> bool {
+		app.is_some_and(|app| self.ignored.lock().is_ok_and(|patterns| app.matches(&patterns)))
+	}
+}
+
 struct Handler {
-	tx: UnboundedSender<ClipboardContent>,
+	tx: UnboundedSender<Capture>,
 	last_content: Option<ClipboardContent>,
+	formats: FormatReader,
+	apps: SourceAppReader,
+	controls: CaptureControls,
+}
+
+impl Handler {
+
+	/// read the clipboard and send it on, unless the source asked not to be recorded
+	fn record(&mut self, decision: CaptureDecision, app: Option<SourceApp>) {
+		if decision == CaptureDecision::Ignore {
+			return;
+		}
+
+		let content = read_clipboard(&self.formats);
+
+		// make sure new data is different from last
+		if self.last_content.as_ref() == Some(&content) {
+			return;
+		}
+
+		self.last_content = Some(content.clone());
+		let _ = self.tx.send(Capture {
+			content,
+			concealed: decision == CaptureDecision::Conceal,
+			source: CaptureSource::Clipboard,
+			app,
+		});
+	}
 }
 
 impl ClipboardHandler for Handler {
 	fn on_clipboard_change(&mut self) -> CallbackResult {
 		sleep(time::Duration::from_millis(50)); // https://learn.microsoft.com/en-us/answers/questions/1327362/wm-clipboardupdate-issue
-		let content = read_clipboard();
 
-		// make sure new data is different from last
-		if self.last_content.as_ref() == Some(&content) {
+		// while paused, or for copies made in ignored apps, the clipboard isn't even read
+		if self.controls.pause.is_paused() {
+			return CallbackResult::Next;
+		}
+		let app = self.apps.current();
+		if self.controls.is_ignored(app.as_ref()) {
 			return CallbackResult::Next;
 		}
 
-		self.last_content = Some(content.clone());
-		let _ = self.tx.send(content);
+		// password managers mark copies that shouldn't end up in history
+		let decision = self.formats.decision();
+		self.record(decision, app);
 		CallbackResult::Next
 	}
 }
 
-fn read_clipboard() -> ClipboardContent {
+/// read the richest representation on the clipboard, files first, then formatted text, then plain text
+pub fn read_clipboard(formats: &FormatReader) -> ClipboardContent {
 	let Ok(mut clipboard) = Clipboard::new() else {
 		return ClipboardContent::Empty;
 	};
 
-	if let Ok(text) = clipboard.get_text() {
-		return ClipboardContent::Text(text);
+	if let Ok(files) = clipboard.get().file_list() {
+		if !files.is_empty() {
+			return ClipboardContent::Files(files);
+		}
+	}
+
+	let text = clipboard.get_text().ok();
+	if let Ok(html) = clipboard.get().html() {
+		let text = text.unwrap_or_else(|| html_to_text(&html));
+		return ClipboardContent::Html { html, text };
+	}
+	if let Some(rtf) = formats.read(RTF_FORMAT) {
+		return ClipboardContent::Rtf { rtf: String::from_utf8_lossy(&rtf).into_owned(), text: text.unwrap_or_default() };
+	}
+	if let Some(text) = text {
+		return ClipboardContent::from_text(text);
 	}
 
 	if let Ok(img) = clipboard.get_image() {
@@ -69,14 +302,108 @@ fn read_clipboard() -> ClipboardContent {
 	ClipboardContent::Empty
 }
 
-pub fn start_listener() -> UnboundedReceiver<ClipboardContent> {
+/// rough plain text for html copied without a text alternative
+fn html_to_text(html: &str) -> String {
+	static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<(?:script|style)\b.*?</(?:script|style)>|<[^>]*>").unwrap());
+	TAG.replace_all(html, "")
+		.replace("&nbsp;", " ")
+		.replace("&lt;", "<")
+		.replace("&gt;", ">")
+		.replace("&quot;", "\"")
+		.replace("&#39;", "'")
+		.replace("&amp;", "&")
+		.trim()
+		.to_string()
+}
+
+/// native wayland clients don't raise X11 selection events, so watch the selection through the
+/// ext/wlr data-control protocols instead
+#[cfg(all(unix, not(target_os = "macos")))]
+mod wayland {
+	use std::io::Read;
+	use wl_clipboard_rs::paste::{Error, Seat};
+	use wl_clipboard_rs::watch::{ClipboardEvent, ClipboardType, Watcher};
+	use crate::capture_hints::capture_decision;
+	use super::Handler;
+
+	/// blocks until the compositor goes away, errors straight away if it has no data-control support
+	pub fn watch(handler: &mut Handler) -> Result<(), Error> {
+		let mut watcher = Watcher::new(ClipboardType::Regular, Seat::Unspecified)?;
+
+		// the first event is whatever was already on the clipboard at startup
+		let mut seen_initial = false;
+		while let Some(event) = watcher.next_event()? {
+			let ClipboardEvent::Changed { mime_types, mut offer, .. } = event else {
+				continue;
+			};
+			if !std::mem::replace(&mut seen_initial, true) {
+				continue;
+			}
+			if handler.controls.pause.is_paused() {
+				continue;
+			}
+			let app = handler.apps.current();
+			if handler.controls.is_ignored(app.as_ref()) {
+				continue;
+			}
+
+			let decision = capture_decision(&mime_types, |mime_type| {
+				let mut value = Vec::new();
+				offer.receive(mime_type).ok()?.read_to_end(&mut value).ok()?;
+				Some(value)
+			});
+			handler.record(decision, app);
+		}
+		Ok(())
+	}
+}
+
+/// watch the clipboard in the background, and the PRIMARY selection too while `controls.primary` is on
+pub fn start_listener(controls: CaptureControls) -> UnboundedReceiver<Capture> {
 	let (tx, rx) = mpsc::unbounded_channel();
+	primary::start_listener(tx.clone(), controls.clone());
 
 	thread::spawn(move || {
-		let handler = Handler { tx, last_content: None };
+		#[allow(unused_mut)] // only the wayland path borrows it mutably
+		let mut handler = Handler { tx, last_content: None, formats: FormatReader::new(), apps: SourceAppReader::new(), controls };
+
+		#[cfg(all(unix, not(target_os = "macos")))]
+		if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+			match wayland::watch(&mut handler) {
+				Ok(()) => return,
+				Err(err) => eprintln!("Wayland clipboard watching unavailable, falling back to X11: {err}"),
+			}
+		}
+
+		// XFixes selection events on X11, WM_CLIPBOARDUPDATE on windows
 		let mut master = Master::new(handler).unwrap();
 		master.run().unwrap();
 	});
 
 	rx
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use chrono::Duration;
+
+	#[test]
+	fn pauses_last_until_resumed_or_their_time_is_up() {
+		let pause = CapturePause::default();
+		let now = Local::now();
+		assert_eq!(pause.state_at(now), CaptureState::Recording);
+
+		pause.pause(None);
+		assert_eq!(pause.state_at(now + Duration::days(1)), CaptureState::Paused { until: None });
+		pause.resume();
+		assert_eq!(pause.state_at(now), CaptureState::Recording);
+
+		let until = now + Duration::minutes(5);
+		pause.pause(Some(until));
+		assert_eq!(pause.state_at(now), CaptureState::Paused { until: Some(until) });
+		assert_eq!(pause.state_at(until), CaptureState::Recording);
+		// once over it stays over
+		assert_eq!(pause.state_at(now), CaptureState::Recording);
+	}
+}
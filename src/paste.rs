@@ -0,0 +1,258 @@
+use std::sync::Arc;
+use std::time::Duration;
+use crate::source_app::SourceApp;
+
+/// how long the previous window gets to take focus back before the keystroke is sent
+const FOCUS_WAIT: Duration = Duration::from_millis(300);
+
+/// how long the user gets to let go of the hotkey's modifiers, they'd be added to the paste keystroke
+const MODIFIER_WAIT: Duration = Duration::from_secs(2);
+
+/// the keystroke that pastes in the target app
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasteKeys {
+	CtrlV,
+	/// terminals keep ctrl+v for themselves
+	CtrlShiftV,
+}
+
+impl PasteKeys {
+	/// ctrl+shift+v in apps matching one of `shift_apps`, ctrl+v everywhere else
+	pub fn for_app(app: Option<&SourceApp>, shift_apps: &[String]) -> Self {
+		let patterns: Vec<String> = shift_apps.iter().map(|pattern| pattern.trim().to_string()).filter(|pattern| !pattern.is_empty()).collect();
+		match app {
+			Some(app) if app.matches(&patterns) => PasteKeys::CtrlShiftV,
+			_ => PasteKeys::CtrlV,
+		}
+	}
+}
+
+/// the window that was focused before the popup opened, where the paste goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasteTarget {
+	/// an HWND on windows, an X11 window id on linux
+	window: u64,
+	pub keys: PasteKeys,
+}
+
+pub type SharedPaster = Arc<Paster>;
+
+/// focuses windows and types into them through the win32 api
+#[cfg(windows)]
+#[derive(Default)]
+pub struct Paster;
+
+#[cfg(windows)]
+impl Paster {
+	pub fn new() -> Self {
+		Self
+	}
+
+	/// the foreground window, call before the popup takes focus
+	pub fn target(&self, app: Option<&SourceApp>, shift_apps: &[String]) -> Option<PasteTarget> {
+		use windows_sys::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+
+		// SAFETY: no arguments, returns null when nothing is focused
+		let window = unsafe { GetForegroundWindow() };
+		(!window.is_null()).then(|| PasteTarget { window: window as u64, keys: PasteKeys::for_app(app, shift_apps) })
+	}
+
+	/// bring `target` back to the front and press its paste keys, blocks for a moment
+	pub fn paste(&self, target: PasteTarget) -> anyhow::Result<()> {
+		use windows_sys::Win32::UI::Input::KeyboardAndMouse::{SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYEVENTF_KEYUP, VK_CONTROL, VK_SHIFT, VK_V};
+		use windows_sys::Win32::UI::WindowsAndMessaging::SetForegroundWindow;
+
+		wait_for_modifiers(|| {
+			use windows_sys::Win32::UI::Input::KeyboardAndMouse::{GetAsyncKeyState, VK_LWIN, VK_MENU, VK_RWIN};
+			// SAFETY: only reads key state, the high bit is set while the key is down
+			[VK_CONTROL, VK_SHIFT, VK_MENU, VK_LWIN, VK_RWIN].iter().any(|&key| unsafe { GetAsyncKeyState(i32::from(key)) } < 0)
+		})?;
+
+		// SAFETY: a stale HWND only makes SetForegroundWindow fail
+		if unsafe { SetForegroundWindow(target.window as usize as _) } == 0 {
+			anyhow::bail!("the window that was focused before couldn't be brought back");
+		}
+		std::thread::sleep(FOCUS_WAIT);
+
+		let keys = match target.keys {
+			PasteKeys::CtrlV => vec![VK_CONTROL, VK_V],
+			PasteKeys::CtrlShiftV => vec![VK_CONTROL, VK_SHIFT, VK_V],
+		};
+		let input = |key, flags| INPUT {
+			r#type: INPUT_KEYBOARD,
+			Anonymous: INPUT_0 { ki: KEYBDINPUT { wVk: key, wScan: 0, dwFlags: flags, time: 0, dwExtraInfo: 0 } },
+		};
+		// press in order, release in reverse
+		let inputs: Vec<INPUT> = keys.iter().map(|&key| input(key, 0)).chain(keys.iter().rev().map(|&key| input(key, KEYEVENTF_KEYUP))).collect();
+		// SAFETY: the pointer and count describe `inputs`, which outlives the call
+		let sent = unsafe { SendInput(inputs.len() as u32, inputs.as_ptr(), std::mem::size_of::<INPUT>() as i32) };
+		if sent as usize != inputs.len() {
+			anyhow::bail!("the paste keystroke was blocked, the target may be running as administrator");
+		}
+		Ok(())
+	}
+}
+
+/// focuses windows through the window manager and types into them with XTest, holds on to its connection
+/// wayland doesn't let clients focus other windows or fake input, so it only copies there
+#[cfg(all(unix, not(target_os = "macos")))]
+pub struct Paster {
+	x11: Option<(x11rb::rust_connection::RustConnection, usize)>,
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl Default for Paster {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl Paster {
+	pub fn new() -> Self {
+		let x11 = std::env::var_os("WAYLAND_DISPLAY").is_none().then(|| x11rb::connect(None).ok()).flatten();
+		Self { x11 }
+	}
+
+	/// the active window, call before the popup takes focus
+	pub fn target(&self, app: Option<&SourceApp>, shift_apps: &[String]) -> Option<PasteTarget> {
+		use x11rb::connection::Connection;
+
+		let (connection, screen) = self.x11.as_ref()?;
+		let window = active_window(connection, connection.setup().roots[*screen].root)?;
+		Some(PasteTarget { window: u64::from(window), keys: PasteKeys::for_app(app, shift_apps) })
+	}
+
+	/// ask the window manager to activate `target` and press its paste keys, blocks for a moment
+	pub fn paste(&self, target: PasteTarget) -> anyhow::Result<()> {
+		use anyhow::Context;
+		use x11rb::connection::Connection;
+		use x11rb::protocol::xproto::{ClientMessageEvent, ConnectionExt, EventMask, KEY_PRESS_EVENT, KEY_RELEASE_EVENT};
+		use x11rb::protocol::xtest::ConnectionExt as _;
+		use x11rb::CURRENT_TIME;
+
+		let (connection, screen) = self.x11.as_ref().context("pasting into other windows only works on X11")?;
+		let root = connection.setup().roots[*screen].root;
+		let window = target.window as u32;
+
+		let modifiers = connection.get_modifier_mapping()?.reply()?.keycodes;
+		wait_for_modifiers(|| {
+			let Some(keymap) = connection.query_keymap().ok().and_then(|cookie| cookie.reply().ok()) else {
+				return false;
+			};
+			modifiers.iter().any(|&keycode| keycode != 0 && keymap.keys[usize::from(keycode / 8)] & (1 << (keycode % 8)) != 0)
+		})?;
+
+		// source 2 says a pager asked, window managers ignore focus stealing rules for those
+		let active = connection.intern_atom(false, b"_NET_ACTIVE_WINDOW")?.reply()?.atom;
+		let event = ClientMessageEvent::new(32, window, active, [2, CURRENT_TIME, 0, 0, 0]);
+		connection.send_event(false, root, EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY, event)?;
+		connection.flush()?;
+		let step = Duration::from_millis(10);
+		let mut waited = Duration::ZERO;
+		while active_window(connection, root) != Some(window) {
+			if waited >= FOCUS_WAIT {
+				anyhow::bail!("the window that was focused before didn't take focus back");
+			}
+			std::thread::sleep(step);
+			waited += step;
+		}
+
+		const CONTROL_L: u32 = 0xffe3;
+		const SHIFT_L: u32 = 0xffe1;
+		const V: u32 = 0x0076;
+		let keysyms = match target.keys {
+			PasteKeys::CtrlV => vec![CONTROL_L, V],
+			PasteKeys::CtrlShiftV => vec![CONTROL_L, SHIFT_L, V],
+		};
+		let setup = connection.setup();
+		let (first, count) = (setup.min_keycode, setup.max_keycode - setup.min_keycode + 1);
+		let mapping = connection.get_keyboard_mapping(first, count)?.reply()?;
+		let per_keycode = usize::from(mapping.keysyms_per_keycode).max(1);
+		let keycodes = keysyms
+			.iter()
+			.map(|keysym| {
+				let index = mapping.keysyms.chunks(per_keycode).position(|syms| syms.contains(keysym))?;
+				Some(first + index as u8)
+			})
+			.collect::<Option<Vec<u8>>>()
+			.context("the keyboard layout has no key for the paste shortcut")?;
+
+		// press in order, release in reverse
+		for &keycode in &keycodes {
+			connection.xtest_fake_input(KEY_PRESS_EVENT, keycode, CURRENT_TIME, root, 0, 0, 0)?;
+		}
+		for &keycode in keycodes.iter().rev() {
+			connection.xtest_fake_input(KEY_RELEASE_EVENT, keycode, CURRENT_TIME, root, 0, 0, 0)?;
+		}
+		// a round trip so a missing XTest extension shows up here rather than never
+		connection.get_input_focus()?.reply()?;
+		Ok(())
+	}
+}
+
+/// block until `held` says no modifier keys are down, the hotkey that triggered the paste may still be held
+#[cfg(any(windows, all(unix, not(target_os = "macos"))))]
+fn wait_for_modifiers(held: impl Fn() -> bool) -> anyhow::Result<()> {
+	let step = Duration::from_millis(10);
+	let mut waited = Duration::ZERO;
+	while held() {
+		if waited >= MODIFIER_WAIT {
+			anyhow::bail!("modifier keys were still held down, pasting would have pressed a different shortcut");
+		}
+		std::thread::sleep(step);
+		waited += step;
+	}
+	Ok(())
+}
+
+/// `_NET_ACTIVE_WINDOW` on the root window, `None` when nothing is active
+#[cfg(all(unix, not(target_os = "macos")))]
+fn active_window(connection: &x11rb::rust_connection::RustConnection, root: u32) -> Option<u32> {
+	use x11rb::protocol::xproto::{AtomEnum, ConnectionExt};
+
+	let atom = connection.intern_atom(false, b"_NET_ACTIVE_WINDOW").ok()?.reply().ok()?.atom;
+	let reply = connection.get_property(false, root, atom, AtomEnum::WINDOW, 0, 1).ok()?.reply().ok()?;
+	let window = reply.value32()?.next()?;
+	(window != 0).then_some(window)
+}
+
+/// no auto-paste on other platforms, entries are only copied
+#[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+#[derive(Default)]
+pub struct Paster;
+
+#[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+impl Paster {
+	pub fn new() -> Self {
+		Self
+	}
+
+	pub fn target(&self, _app: Option<&SourceApp>, _shift_apps: &[String]) -> Option<PasteTarget> {
+		None
+	}
+
+	pub fn paste(&self, _target: PasteTarget) -> anyhow::Result<()> {
+		anyhow::bail!("pasting into other windows isn't supported here")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn app(name: &str, class: &str) -> SourceApp {
+		SourceApp { name: name.to_string(), title: String::new(), class: class.to_string() }
+	}
+
+	#[test]
+	fn terminals_get_ctrl_shift_v() {
+		let shift_apps = ["*term*".to_string(), " kitty ".to_string(), String::new()];
+		assert_eq!(PasteKeys::for_app(Some(&app("gnome-terminal-", "Gnome-terminal")), &shift_apps), PasteKeys::CtrlShiftV);
+		assert_eq!(PasteKeys::for_app(Some(&app("kitty", "")), &shift_apps), PasteKeys::CtrlShiftV);
+		assert_eq!(PasteKeys::for_app(Some(&app("firefox", "Navigator")), &shift_apps), PasteKeys::CtrlV);
+		// an empty pattern would only match apps without a name
+		assert_eq!(PasteKeys::for_app(Some(&app("", "")), &shift_apps), PasteKeys::CtrlV);
+		assert_eq!(PasteKeys::for_app(None, &shift_apps), PasteKeys::CtrlV);
+	}
+}
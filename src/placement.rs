@@ -0,0 +1,172 @@
+/// space left between the popup and what it opens at
+const GAP: f64 = 4.0;
+
+/// a screen rectangle in physical pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+	pub x: f64,
+	pub y: f64,
+	pub width: f64,
+	pub height: f64,
+}
+
+impl Rect {
+	/// a zero-sized rectangle, e.g. the mouse pointer
+	pub fn point(x: f64, y: f64) -> Self {
+		Self { x, y, width: 0.0, height: 0.0 }
+	}
+
+	fn right(&self) -> f64 {
+		self.x + self.width
+	}
+
+	fn bottom(&self) -> f64 {
+		self.y + self.height
+	}
+
+	/// the overlap of both, `None` when they don't touch
+	#[cfg_attr(not(all(unix, not(target_os = "macos"))), allow(dead_code))]
+	pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+		let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+		let (right, bottom) = (self.right().min(other.right()), self.bottom().min(other.bottom()));
+		(right > x && bottom > y).then_some(Rect { x, y, width: right - x, height: bottom - y })
+	}
+}
+
+/// top-left corner of a `width`×`height` popup inside `area`
+/// just below `anchor` if it fits, above it if not, centered without one
+pub fn place(anchor: Option<Rect>, width: f64, height: f64, area: Rect) -> (f64, f64) {
+	let (x, y) = match anchor {
+		Some(anchor) => {
+			let below = anchor.bottom() + GAP;
+			let y = if below + height > area.bottom() && anchor.y - GAP - height >= area.y { anchor.y - GAP - height } else { below };
+			(anchor.x, y)
+		}
+		None => (area.x + (area.width - width) / 2.0, area.y + (area.height - height) / 2.0),
+	};
+	// pushed back inside, popups bigger than the area stick to its top-left corner
+	let x = x.min(area.right() - width).max(area.x);
+	let y = y.min(area.bottom() - height).max(area.y);
+	(x, y)
+}
+
+/// the text caret of the focused window, read through the win32 gui thread info
+/// apps drawing their own caret (most browsers and electron apps) don't report one
+#[cfg(windows)]
+pub fn caret() -> Option<Rect> {
+	use windows_sys::Win32::Foundation::POINT;
+	use windows_sys::Win32::Graphics::Gdi::ClientToScreen;
+	use windows_sys::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetGUIThreadInfo, GetWindowThreadProcessId, GUITHREADINFO};
+
+	// SAFETY: plain win32 calls, `info` is sized through `cbSize` and the point is ours
+	unsafe {
+		let window = GetForegroundWindow();
+		if window.is_null() {
+			return None;
+		}
+		let thread = GetWindowThreadProcessId(window, std::ptr::null_mut());
+		let mut info = GUITHREADINFO { cbSize: std::mem::size_of::<GUITHREADINFO>() as u32, ..Default::default() };
+		if GetGUIThreadInfo(thread, &mut info) == 0 || info.hwndCaret.is_null() {
+			return None;
+		}
+		let caret = info.rcCaret;
+		let mut corner = POINT { x: caret.left, y: caret.top };
+		if ClientToScreen(info.hwndCaret, &mut corner) == 0 {
+			return None;
+		}
+		Some(Rect { x: f64::from(corner.x), y: f64::from(corner.y), width: f64::from(caret.right - caret.left), height: f64::from(caret.bottom - caret.top) })
+	}
+}
+
+/// there's no caret to ask for without accessibility apis, the mouse is used instead
+#[cfg(not(windows))]
+pub fn caret() -> Option<Rect> {
+	None
+}
+
+/// the part of `monitor` not covered by taskbars, from the monitor info
+#[cfg(windows)]
+pub fn work_area(monitor: Rect) -> Rect {
+	use windows_sys::Win32::Foundation::POINT;
+	use windows_sys::Win32::Graphics::Gdi::{GetMonitorInfoW, MonitorFromPoint, MONITORINFO, MONITOR_DEFAULTTONEAREST};
+
+	let center = POINT { x: (monitor.x + monitor.width / 2.0) as i32, y: (monitor.y + monitor.height / 2.0) as i32 };
+	// SAFETY: `info` is sized through `cbSize`, a null monitor only makes GetMonitorInfoW fail
+	unsafe {
+		let handle = MonitorFromPoint(center, MONITOR_DEFAULTTONEAREST);
+		let mut info = MONITORINFO { cbSize: std::mem::size_of::<MONITORINFO>() as u32, ..Default::default() };
+		if GetMonitorInfoW(handle, &mut info) == 0 {
+			return monitor;
+		}
+		let work = info.rcWork;
+		Rect { x: f64::from(work.left), y: f64::from(work.top), width: f64::from(work.right - work.left), height: f64::from(work.bottom - work.top) }
+	}
+}
+
+/// the part of `monitor` not covered by panels, from `_NET_WORKAREA` on X11
+/// that's a single box over all monitors, so it only trims the monitor's outer edges
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn work_area(monitor: Rect) -> Rect {
+	use x11rb::connection::Connection;
+	use x11rb::protocol::xproto::{AtomEnum, ConnectionExt};
+
+	let desktop_area = || -> Option<Rect> {
+		if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+			return None;
+		}
+		let (connection, screen) = x11rb::connect(None).ok()?;
+		let root = connection.setup().roots[screen].root;
+		let atom = |name: &str| Some(connection.intern_atom(false, name.as_bytes()).ok()?.reply().ok()?.atom);
+		let cardinals = |property: u32| -> Option<Vec<u32>> {
+			let reply = connection.get_property(false, root, property, AtomEnum::CARDINAL, 0, 1024).ok()?.reply().ok()?;
+			let values = reply.value32()?.collect();
+			Some(values)
+		};
+		// one x, y, width, height for each virtual desktop
+		let desktop = cardinals(atom("_NET_CURRENT_DESKTOP")?).and_then(|values| values.first().copied()).unwrap_or(0) as usize;
+		let areas = cardinals(atom("_NET_WORKAREA")?)?;
+		let area = areas.chunks_exact(4).nth(desktop).or_else(|| areas.chunks_exact(4).next())?;
+		Some(Rect { x: f64::from(area[0]), y: f64::from(area[1]), width: f64::from(area[2]), height: f64::from(area[3]) })
+	};
+	desktop_area().and_then(|area| area.intersect(&monitor)).unwrap_or(monitor)
+}
+
+#[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+pub fn work_area(monitor: Rect) -> Rect {
+	monitor
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SCREEN: Rect = Rect { x: 0.0, y: 0.0, width: 1920.0, height: 1040.0 };
+
+	#[test]
+	fn popups_open_below_the_anchor_or_above_it() {
+		assert_eq!(place(Some(Rect { x: 100.0, y: 200.0, width: 2.0, height: 18.0 }), 360.0, 480.0, SCREEN), (100.0, 222.0));
+		assert_eq!(place(Some(Rect::point(100.0, 200.0)), 360.0, 480.0, SCREEN), (100.0, 204.0));
+		// no room below the caret
+		assert_eq!(place(Some(Rect { x: 100.0, y: 900.0, width: 2.0, height: 18.0 }), 360.0, 480.0, SCREEN), (100.0, 416.0));
+		assert_eq!(place(None, 360.0, 480.0, SCREEN), (780.0, 280.0));
+	}
+
+	#[test]
+	fn popups_stay_inside_the_work_area() {
+		// a second monitor to the right, with a panel along its top
+		let area = Rect { x: 1920.0, y: 30.0, width: 1280.0, height: 994.0 };
+		assert_eq!(place(Some(Rect::point(3150.0, 500.0)), 360.0, 480.0, area), (2840.0, 504.0));
+		assert_eq!(place(Some(Rect::point(1000.0, 0.0)), 360.0, 480.0, area), (1920.0, 30.0));
+		// no room above or below, it's pushed up as far as the panel allows
+		assert_eq!(place(Some(Rect::point(2000.0, 520.0)), 360.0, 980.0, area), (2000.0, 44.0));
+		assert_eq!(place(None, 5000.0, 5000.0, area), (1920.0, 30.0));
+	}
+
+	#[test]
+	fn rectangles_intersect() {
+		let monitor = Rect { x: 1920.0, y: 0.0, width: 1920.0, height: 1080.0 };
+		let desktop = Rect { x: 0.0, y: 32.0, width: 3840.0, height: 1048.0 };
+		assert_eq!(desktop.intersect(&monitor), Some(Rect { x: 1920.0, y: 32.0, width: 1920.0, height: 1048.0 }));
+		assert_eq!(Rect::point(5.0, 5.0).intersect(&monitor), None);
+	}
+}
@@ -0,0 +1,165 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use crate::monitor::{Capture, CaptureControls};
+
+/// whether middle-click selections are recorded, off unless the user opts in
+pub type PrimaryCapture = Arc<Mutex<bool>>;
+
+/// how long a selection has to stay unchanged before it's read
+#[cfg(all(unix, not(target_os = "macos")))]
+pub const SETTLE_DELAY: Duration = Duration::from_millis(400);
+
+/// a selection that grows or shrinks the previous one within this window replaces it instead of adding an entry
+pub const REPLACE_WINDOW: Duration = Duration::from_secs(3);
+
+/// wait for a burst of change ticks to go quiet for `quiet`, then call `settled` once
+/// returns when the sender goes away
+#[cfg(any(test, all(unix, not(target_os = "macos"))))]
+pub fn debounce(ticks: std::sync::mpsc::Receiver<()>, quiet: Duration, mut settled: impl FnMut()) {
+	while ticks.recv().is_ok() {
+		while ticks.recv_timeout(quiet).is_ok() {}
+		settled();
+	}
+}
+
+/// `next` extends or trims `previous` at one end, as happens while a selection is dragged out
+pub fn is_partial_selection(previous: &str, next: &str) -> bool {
+	previous != next
+		&& (next.starts_with(previous) || next.ends_with(previous) || previous.starts_with(next) || previous.ends_with(next))
+}
+
+/// watch the PRIMARY selection in the background and send settled text selections while `controls.primary` is on
+/// selections are left out while capture is paused or made in an ignored app
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn start_listener(tx: UnboundedSender<Capture>, controls: CaptureControls) {
+	use arboard::{Clipboard, GetExtLinux, LinuxClipboardKind};
+	use std::thread;
+	use crate::monitor::{CaptureSource, ClipboardContent};
+	use crate::source_app::SourceAppReader;
+
+	let (tick_tx, tick_rx) = std::sync::mpsc::channel();
+	thread::spawn(move || watch::changes(tick_tx));
+
+	thread::spawn(move || {
+		let mut last: Option<String> = None;
+		let apps = SourceAppReader::new();
+		debounce(tick_rx, SETTLE_DELAY, || {
+			if !controls.primary.lock().is_ok_and(|enabled| *enabled) || controls.pause.is_paused() {
+				return;
+			}
+			let Ok(mut clipboard) = Clipboard::new() else {
+				return;
+			};
+			let app = apps.current();
+			if controls.is_ignored(app.as_ref()) {
+				return;
+			}
+			let Ok(text) = clipboard.get().clipboard(LinuxClipboardKind::Primary).text() else {
+				return;
+			};
+			if text.trim().is_empty() || last.as_ref() == Some(&text) {
+				return;
+			}
+			last = Some(text.clone());
+			let _ = tx.send(Capture { content: ClipboardContent::Text(text), concealed: false, source: CaptureSource::Primary, app });
+		});
+	});
+}
+
+/// there's no PRIMARY selection elsewhere
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn start_listener(_tx: UnboundedSender<Capture>, _controls: CaptureControls) {}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod watch {
+	use std::sync::mpsc::Sender;
+
+	/// send a tick whenever the PRIMARY selection changes, through data-control on wayland and XFixes on X11
+	pub fn changes(ticks: Sender<()>) {
+		if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+			match wayland(&ticks) {
+				Ok(()) => return,
+				Err(err) => eprintln!("Wayland primary selection watching unavailable, falling back to X11: {err}"),
+			}
+		}
+		if let Err(err) = x11(&ticks) {
+			eprintln!("Failed to watch the primary selection: {err}");
+		}
+	}
+
+	fn wayland(ticks: &Sender<()>) -> Result<(), wl_clipboard_rs::paste::Error> {
+		use wl_clipboard_rs::paste::Seat;
+		use wl_clipboard_rs::watch::{ClipboardType, Watcher};
+
+		let mut watcher = Watcher::new(ClipboardType::Primary, Seat::Unspecified)?;
+		// the first event is the selection that was already there
+		watcher.next_event()?;
+		while watcher.next_event()?.is_some() {
+			if ticks.send(()).is_err() {
+				break;
+			}
+		}
+		Ok(())
+	}
+
+	fn x11(ticks: &Sender<()>) -> anyhow::Result<()> {
+		use x11rb::connection::Connection;
+		use x11rb::protocol::{xfixes, Event};
+
+		let clipboard = x11_clipboard::Clipboard::new()?;
+		let connection = &clipboard.getter.connection;
+		xfixes::query_version(connection, 5, 0)?.reply()?;
+		let root = connection.setup().roots[clipboard.getter.screen].root;
+		xfixes::select_selection_input(
+			connection,
+			root,
+			clipboard.getter.atoms.primary,
+			xfixes::SelectionEventMask::SET_SELECTION_OWNER,
+		)?.check()?;
+
+		loop {
+			if let Event::XfixesSelectionNotify(_) = connection.wait_for_event()? {
+				if ticks.send(()).is_err() {
+					return Ok(());
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::mpsc::channel;
+	use std::thread;
+
+	#[test]
+	fn bursts_settle_once() {
+		let (tx, rx) = channel();
+		let sender = thread::spawn(move || {
+			for _ in 0..10 {
+				tx.send(()).unwrap();
+				thread::sleep(Duration::from_millis(5));
+			}
+			thread::sleep(Duration::from_millis(150));
+			tx.send(()).unwrap();
+		});
+
+		let mut settled = 0;
+		debounce(rx, Duration::from_millis(50), || settled += 1);
+		sender.join().unwrap();
+
+		assert_eq!(settled, 2);
+	}
+
+	#[test]
+	fn partial_selections_grow_or_shrink_at_one_end() {
+		assert!(is_partial_selection("hello", "hello world"));
+		assert!(is_partial_selection("world", "hello world"));
+		assert!(is_partial_selection("hello world", "hello wor"));
+		assert!(!is_partial_selection("hello", "hello"));
+		assert!(!is_partial_selection("lo wo", "hello world"));
+		assert!(!is_partial_selection("hello", "goodbye"));
+	}
+}
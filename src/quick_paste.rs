@@ -1,51 +1,185 @@
-use std::{borrow::Cow, sync::{Arc, Mutex}};
-use arboard::{Clipboard, ImageData};
+use std::sync::{Arc, Mutex};
 use dioxus::desktop::tao::event::{Event, WindowEvent};
+use dioxus::desktop::tao::dpi::PhysicalPosition;
+use dioxus::desktop::tao::window::Window;
 use dioxus::desktop::{use_window, use_wry_event_handler, Config, WindowBuilder};
 use dioxus::prelude::*;
 
-use crate::db::ClipboardEntry;
-use crate::monitor::ClipboardContent;
+use crate::backend::{write_suppressed, ClipboardWriteSuppression, SharedBackend};
+use crate::db::{ClipboardEntry, SharedDatabase, PAGE_SIZE};
+use crate::monitor::{CaptureSource, ClipboardContent};
+use crate::paste::{PasteTarget, SharedPaster};
+use crate::placement::{self, Rect};
+use crate::settings::{PopupPlacement, PopupSettings};
+use crate::transform::{self, Transform};
+use tokio::sync::Notify;
 
 const TAILWIND_CSS: Asset = asset!("/assets/tailwind.css");
-pub type ClipboardWriteSuppression = Arc<Mutex<bool>>;
+/// rows skipped by page up and page down
+const PAGE_STEP: usize = 8;
+/// most matches listed while filtering
+const FILTER_LIMIT: usize = 100;
+/// rows that can be picked with a digit key
+const DIGIT_ROWS: usize = 9;
 
-fn decode_data_uri(data_uri: &str) -> Option<Vec<u8>> {
-	let (_, b64) = data_uri.split_once(";base64,")?;
-	use base64::{engine::general_purpose, Engine as _};
-	general_purpose::STANDARD.decode(b64).ok()
+/// what the main window and the popup share, there's one popup window for the life of the app
+#[derive(Clone, Default)]
+pub struct QuickPasteShared {
+	/// where picks are pasted, the window the popup was last opened over
+	target: Arc<Mutex<Option<PasteTarget>>>,
+	/// selections are listed too, following the main window's toggle
+	show_primary: Arc<Mutex<bool>>,
+	opened: Arc<Notify>,
+	history_changed: Arc<Notify>,
 }
 
-pub fn write_clipboard_content(content: &ClipboardContent) -> anyhow::Result<()> {
-	let mut clipboard = Clipboard::new()?;
-	match content {
-		ClipboardContent::Text(text) => clipboard.set_text(text.clone())?,
-		ClipboardContent::Image(data_uri) => {
-			let bytes = decode_data_uri(data_uri).ok_or_else(|| anyhow::anyhow!("Invalid image data URI"))?;
-			let rgba = image::load_from_memory(&bytes)?.into_rgba8();
-			let (width, height) = rgba.dimensions();
-			clipboard.set_image(ImageData {
-				width: width as usize,
-				height: height as usize,
-				bytes: Cow::Owned(rgba.into_raw()),
-			})?;
+impl QuickPasteShared {
+	/// the popup is being shown over `target`, it starts over with an empty filter
+	pub fn open(&self, target: Option<PasteTarget>) {
+		if let Ok(mut current) = self.target.lock() {
+			*current = target;
 		}
-		ClipboardContent::Empty => {}
+		self.opened.notify_one();
+	}
+
+	/// entries were added, removed or pinned, the popup reloads what it lists
+	pub fn history_changed(&self) {
+		self.history_changed.notify_one();
+	}
+
+	/// list selections or leave them out, like the main window does
+	pub fn set_show_primary(&self, show: bool) {
+		if let Ok(mut current) = self.show_primary.lock() {
+			*current = show;
+		}
+		self.history_changed.notify_one();
+	}
+
+	fn target(&self) -> Option<PasteTarget> {
+		self.target.lock().ok().and_then(|target| *target)
+	}
+
+	fn show_primary(&self) -> bool {
+		self.show_primary.lock().is_ok_and(|show| *show)
 	}
-	Ok(())
 }
 
 /// compact popup window that shows the clipboard history list, opens with ctrl+shift+v
-/// closes itself when it loses focus
+/// typing filters the list, arrows move the selection, enter pastes and escape closes
+/// shift+enter pastes plain text, ctrl+enter picks a transformation to paste through
+/// picked entries are pasted into the window the popup opened over, or only copied when there's none
+/// hides itself when it loses focus, and is shown again rather than recreated
 #[component]
-pub fn QuickPaste(entries: Vec<ClipboardEntry>) -> Element {
+pub fn QuickPaste() -> Element {
 	let window = use_window();
-	let entry_count = entries.len();
+	let db = use_context::<SharedDatabase>();
+	let suppression = use_context::<ClipboardWriteSuppression>();
+	let backend = use_context::<SharedBackend>();
+	let paster = use_context::<SharedPaster>();
+	let shared = use_context::<QuickPasteShared>();
 
-	// focus the popup on open
-	let window_for_focus = window.clone();
-	use_effect(move || {
-		window_for_focus.set_focus();
+	// pinned entries stay on top of the list
+	let mut pinned = use_signal(Vec::<ClipboardEntry>::new);
+	// pages of history, newest first
+	let mut entries = use_signal(Vec::<ClipboardEntry>::new);
+	let mut has_more = use_signal(|| true);
+	// bumped by every reload, so filtered matches are looked up again too
+	let mut revision = use_signal(|| 0u64);
+	let db_for_pages = db.clone();
+	let shared_for_pages = shared.clone();
+	let mut load_more = move || {
+		if !has_more() {
+			return;
+		}
+		let cursor = entries.read().last().map(|e| e.cursor());
+		let show_primary = shared_for_pages.show_primary();
+		let page = db_for_pages.lock().ok().and_then(|db_guard| db_guard.load_page(cursor, PAGE_SIZE, show_primary).ok()).unwrap_or_default();
+		has_more.set(page.len() == PAGE_SIZE);
+		entries.write().extend(page);
+	};
+	// as many entries as are loaded, again, picking up what was copied since
+	let db_for_reload = db.clone();
+	let shared_for_reload = shared.clone();
+	let reload = use_callback(move |()| {
+		let count = entries.peek().len().max(PAGE_SIZE);
+		let show_primary = shared_for_reload.show_primary();
+		let Ok(db_guard) = db_for_reload.lock() else {
+			return;
+		};
+		let page = db_guard.load_page(None, count, show_primary).unwrap_or_default();
+		has_more.set(page.len() == count);
+		pinned.set(db_guard.load_pinned().unwrap_or_default());
+		entries.set(page);
+		revision += 1;
+	});
+	use_hook(|| reload(()));
+
+	let mut filter = use_signal(String::new);
+	// keyword matches, best first, `None` while there's nothing to filter by
+	let db_for_filter = db.clone();
+	let shared_for_filter = shared.clone();
+	let matches = use_memo(move || {
+		revision();
+		let filter = filter();
+		if filter.trim().is_empty() {
+			return None;
+		}
+		let Ok(db_guard) = db_for_filter.lock() else {
+			return Some(Vec::new());
+		};
+		let ids = db_guard.search_text(&filter, FILTER_LIMIT).unwrap_or_default().into_iter().map(|hit| hit.id).collect::<Vec<_>>();
+		let mut found = db_guard.load_by_ids(&ids).unwrap_or_default();
+		let show_primary = shared_for_filter.show_primary();
+		found.retain(|entry| show_primary || entry.source != CaptureSource::Primary);
+		found.sort_by_key(|entry| ids.iter().position(|id| *id == entry.id));
+		Some(found)
+	});
+	let rows = use_memo(move || match matches() {
+		Some(found) => found,
+		None => pinned.read().iter().cloned().chain(entries.read().iter().cloned()).collect::<Vec<_>>(),
+	});
+	let entry_count = rows.read().len();
+	let mut selected = use_signal(|| 0usize);
+	// the highlighted transformation while the list of them is open, for the selected row
+	let mut transform_choice = use_signal(|| None::<usize>);
+	let selected_id = rows.read().get(selected()).map(|entry| entry.id);
+	// whether the list of transformations is open, not which one is highlighted
+	let choosing = use_memo(move || transform_choice().is_some());
+	// what works on the selected row, worked out once each time the list opens
+	let choices = use_memo(move || {
+		if !choosing() {
+			return Vec::new();
+		}
+		rows.read()
+			.get(selected())
+			.map(|entry| transform::TRANSFORMS.into_iter().filter(|transform| transform.apply(&entry.content).is_ok()).collect::<Vec<Transform>>())
+			.unwrap_or_default()
+	});
+
+	// each opening starts from the top with an empty filter
+	let opened = shared.clone();
+	use_future(move || {
+		let opened = opened.clone();
+		async move {
+			loop {
+				opened.opened.notified().await;
+				filter.set(String::new());
+				selected.set(0);
+				transform_choice.set(None);
+				reload(());
+				document::eval("document.getElementById('qp-list').scrollTop = 0; document.getElementById('qp-filter')?.focus()");
+			}
+		}
+	});
+	let changed = shared.clone();
+	use_future(move || {
+		let changed = changed.clone();
+		async move {
+			loop {
+				changed.history_changed.notified().await;
+				reload(());
+			}
+		}
 	});
 
 	// close when the window loses focus
@@ -56,87 +190,264 @@ pub fn QuickPaste(entries: Vec<ClipboardEntry>) -> Element {
 		}
 	});
 
+	let window_for_paste = window.clone();
+	let paste = use_callback(move |(entry_id, transform): (i64, Option<Transform>)| {
+		// list rows don't carry image payloads, fetch the full entry
+		let Some(entry) = db.lock().ok().and_then(|db_guard| db_guard.load_entry(entry_id).ok().flatten()) else {
+			return;
+		};
+		let content = match transform.map(|transform| transform.apply(&entry.content)) {
+			Some(Ok(content)) => content,
+			Some(Err(err)) => {
+				eprintln!("Failed to transform selected clipboard item: {err:#}");
+				return;
+			}
+			None => entry.content,
+		};
+		if let Err(err) = write_suppressed(&*backend, &suppression, entry_id, &content) {
+			eprintln!("Failed to write selected clipboard item: {err}");
+			return;
+		}
+		window_for_paste.set_visible(false);
+		if let Some(target) = shared.target() {
+			let paster = paster.clone();
+			tokio::task::spawn_blocking(move || {
+				if let Err(err) = paster.paste(target) {
+					eprintln!("Failed to paste into the previous window: {err:#}");
+				}
+			});
+		}
+	});
+
+	// moving past the loaded rows scrolls the list, which loads the next page
+	let mut select = move |index: usize| {
+		let Some(last) = rows.read().len().checked_sub(1) else {
+			return;
+		};
+		let index = index.min(last);
+		selected.set(index);
+		let id = rows.read()[index].id;
+		document::eval(&format!("document.getElementById('qp-row-{id}')?.scrollIntoView({{ block: 'nearest' }})"));
+	};
+	let paste_row = move |index: usize, transform: Option<Transform>| {
+		let id = rows.read().get(index).map(|entry| entry.id);
+		if let Some(id) = id {
+			paste((id, transform));
+		}
+	};
+
+	let onkeydown = move |evt: KeyboardEvent| {
+		let current = selected();
+		// the transformation list takes the arrows and enter while it's open
+		if let Some(choice) = transform_choice() {
+			match evt.key() {
+				Key::ArrowDown => transform_choice.set(Some((choice + 1).min(choices.read().len().saturating_sub(1)))),
+				Key::ArrowUp => transform_choice.set(Some(choice.saturating_sub(1))),
+				Key::Enter => {
+					let transform = choices.read().get(choice).copied();
+					if let Some(transform) = transform {
+						paste_row(current, Some(transform));
+					}
+					transform_choice.set(None);
+				}
+				Key::Escape => transform_choice.set(None),
+				_ => return,
+			}
+			evt.prevent_default();
+			return;
+		}
+		let modifiers = evt.modifiers();
+		match evt.key() {
+			Key::ArrowDown => select(current + 1),
+			Key::ArrowUp => select(current.saturating_sub(1)),
+			Key::PageDown => select(current + PAGE_STEP),
+			Key::PageUp => select(current.saturating_sub(PAGE_STEP)),
+			Key::Home => select(0),
+			Key::End => select(usize::MAX),
+			Key::Enter if modifiers.shift() => paste_row(current, Some(transform::PLAIN_TEXT)),
+			Key::Enter if modifiers.ctrl() || modifiers.meta() => {
+				if current < rows.read().len() {
+					transform_choice.set(Some(0));
+				}
+			}
+			Key::Enter => paste_row(current, None),
+			Key::Escape => window.set_visible(false),
+			_ => {
+				// plain digits go to the filter once there's something typed, alt+digit always picks
+				let picks = modifiers.alt() || (modifiers.is_empty() && filter.read().is_empty());
+				match digit_row(evt.code()) {
+					Some(index) if picks => paste_row(index, None),
+					_ => return,
+				}
+			}
+		}
+		evt.prevent_default();
+	};
+
 	rsx! {
-        Stylesheet { href: TAILWIND_CSS }
-        style { "
-            ::-webkit-scrollbar {{ width: 6px; }}
-            ::-webkit-scrollbar-track {{ background: transparent; }}
-            ::-webkit-scrollbar-thumb {{ background: #334155; border-radius: 3px; }}
-        " }
-
-        div {
-            class: "h-screen w-screen bg-slate-950 text-slate-200 flex flex-col font-sans overflow-hidden rounded-xl border border-slate-800 shadow-2xl",
-
-            // header bar
-            div {
-                class: "shrink-0 px-3 py-2 border-b border-slate-800 flex items-center gap-2",
-                span { class: "text-xs font-semibold text-slate-400 uppercase tracking-widest", "Clipboard" }
-	                span { class: "ml-auto text-xs text-slate-600", "{entry_count} items" }
-            }
-
-            // clip list
-            div { class: "flex-1 overflow-y-auto",
-	                if entries.is_empty() {
-                    div { class: "flex flex-col items-center justify-center h-full text-slate-600 gap-2",
-                        div { class: "text-3xl opacity-20", "📋" }
-                        p { class: "text-xs", "No clipboard history" }
-                    }
-                }
-	                for entry in entries.iter().cloned() {
-                    QuickPasteRow { entry }
-                }
-            }
-        }
-    }
+		Stylesheet { href: TAILWIND_CSS }
+		style { "
+			::-webkit-scrollbar {{ width: 6px; }}
+			::-webkit-scrollbar-track {{ background: transparent; }}
+			::-webkit-scrollbar-thumb {{ background: #334155; border-radius: 3px; }}
+		" }
+
+		div {
+			class: "relative h-screen w-screen bg-slate-950 text-slate-200 flex flex-col font-sans overflow-hidden rounded-xl border border-slate-800 shadow-2xl",
+			onkeydown,
+
+			// filter
+			div {
+				class: "shrink-0 px-3 py-2 border-b border-slate-800 flex items-center gap-2",
+				input {
+					id: "qp-filter",
+					class: "flex-1 min-w-0 bg-transparent text-sm text-slate-200 placeholder-slate-500 focus:outline-none",
+					placeholder: "Filter clipboard...",
+					value: "{filter}",
+					oninput: move |e| {
+						filter.set(e.value());
+						selected.set(0);
+						transform_choice.set(None);
+						document::eval("document.getElementById('qp-list').scrollTop = 0");
+					},
+					onmounted: move |evt| { spawn(async move { let _ = evt.set_focus(true).await; }); }
+				}
+				span { class: "shrink-0 text-xs text-slate-600", "{entry_count} items" }
+			}
+
+			// clip list
+			div { id: "qp-list", class: "flex-1 overflow-y-auto",
+				onscroll: move |evt| {
+					if matches.read().is_none() && evt.scroll_top() + f64::from(evt.client_height()) >= f64::from(evt.scroll_height()) - 120.0 {
+						load_more();
+					}
+				},
+				if entry_count == 0 {
+					div { class: "flex flex-col items-center justify-center h-full text-slate-600 gap-2",
+						div { class: "text-3xl opacity-20", "📋" }
+						p { class: "text-xs", if matches.read().is_some() { "No matches" } else { "No clipboard history" } }
+					}
+				}
+				for (index, entry) in rows.read().iter().cloned().enumerate() {
+					QuickPasteRow {
+						key: "{entry.id}",
+						entry,
+						selected: index == selected(),
+						digit: (index < DIGIT_ROWS).then_some(index + 1),
+						on_paste: move |id| paste((id, None)),
+					}
+				}
+			}
+
+			div { class: "shrink-0 px-3 py-1.5 border-t border-slate-800 text-[11px] text-slate-600",
+				"Enter paste · Shift+Enter plain text · Ctrl+Enter transform"
+			}
+
+			if let (Some(choice), Some(id)) = (transform_choice(), selected_id) {
+				div { class: "absolute inset-x-3 bottom-3 max-h-[70%] overflow-y-auto bg-slate-900 border border-slate-700 rounded-lg shadow-xl py-1",
+					p { class: "px-3 py-1 text-xs text-slate-500", "Paste as" }
+					if choices.read().is_empty() {
+						p { class: "px-3 py-1.5 text-sm text-slate-500 italic", "Nothing applies to this entry" }
+					}
+					for (index, transform) in choices().into_iter().enumerate() {
+						button {
+							key: "{transform.name}",
+							class: if index == choice { "w-full px-3 py-1.5 text-left text-sm text-slate-200 bg-blue-500/20" } else { "w-full px-3 py-1.5 text-left text-sm text-slate-200 hover:bg-slate-800" },
+							onclick: move |_| {
+								transform_choice.set(None);
+								paste((id, Some(transform)));
+							},
+							"{transform.label}"
+						}
+					}
+				}
+			}
+		}
+	}
 }
 
-/// a single row in the quickpaste list
+/// the row a digit key picks, `Digit1` is the first
+fn digit_row(code: Code) -> Option<usize> {
+	let digit: usize = code.to_string().strip_prefix("Digit")?.parse().ok()?;
+	(1..=DIGIT_ROWS).contains(&digit).then(|| digit - 1)
+}
+
+/// a single row in the quickpaste list, with the digit that picks it if it has one
 #[component]
-fn QuickPasteRow(entry: ClipboardEntry) -> Element {
-	let window = use_window();
-	let suppression = use_context::<ClipboardWriteSuppression>();
+fn QuickPasteRow(entry: ClipboardEntry, selected: bool, digit: Option<usize>, on_paste: EventHandler<i64>) -> Element {
 	let time_str = entry.copied_at.format("%b %d, %I:%M %p").to_string();
-	let selected_content = entry.content.clone();
+	let entry_id = entry.id;
+	let highlight = if selected { "bg-blue-500/20" } else { "hover:bg-slate-800/50" };
 
 	rsx! {
-        div {
-            class: "px-3 py-2 border-b border-slate-800/60 hover:bg-slate-800/50 cursor-pointer transition-colors",
-	            onclick: move |_| {
-	                if let Ok(mut suppressed) = suppression.lock() {
-	                    *suppressed = true;
-	                }
-
-	                if let Err(err) = write_clipboard_content(&selected_content) {
-	                    if let Ok(mut suppressed) = suppression.lock() {
-	                        *suppressed = false;
-	                    }
-	                    eprintln!("Failed to write selected clipboard item: {err}");
-	                    return;
-	                }
-
-	                window.set_visible(false);
-	            },
-            div { class: "text-xs text-slate-500 mb-0.5", "{time_str}" }
-            match entry.content {
-                ClipboardContent::Text(ref text) => rsx! {
-                    p {
-                        class: "text-sm font-mono truncate text-slate-200",
-                        "{text}"
-                    }
-                },
-                ClipboardContent::Image(_) => rsx! {
-                    p { class: "text-xs text-slate-400 italic", "🖼 Image" }
-                },
-                ClipboardContent::Empty => rsx! {
-                    p { class: "text-xs text-slate-600 italic", "Empty" }
-                },
-            }
-        }
-    }
+		div {
+			id: "qp-row-{entry_id}",
+			class: "px-3 py-2 border-b border-slate-800/60 cursor-pointer transition-colors {highlight}",
+			onclick: move |_| on_paste.call(entry_id),
+			div { class: "flex items-center gap-2 text-xs text-slate-500 mb-0.5",
+				span {
+					if entry.pinned { "📌 " }
+					"{time_str}"
+				}
+				if let Some(digit) = digit {
+					span { class: "ml-auto px-1.5 rounded bg-slate-800 text-slate-400 font-mono", "{digit}" }
+				}
+			}
+			match entry.content {
+				ClipboardContent::Text(ref text) | ClipboardContent::Html { ref text, .. } | ClipboardContent::Rtf { ref text, .. } => rsx! {
+					p {
+						class: "text-sm font-mono truncate text-slate-200",
+						"{text}"
+					}
+				},
+				ClipboardContent::Url(ref url) => rsx! {
+					p { class: "text-sm font-mono truncate text-sky-400", "🔗 {url}" }
+				},
+				ClipboardContent::Files(ref paths) => {
+					let names = paths
+						.iter()
+						.map(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|| path.display().to_string()))
+						.collect::<Vec<_>>()
+						.join(", ");
+					rsx! {
+						p { class: "text-sm truncate text-slate-200", "📄 {names}" }
+					}
+				},
+				ClipboardContent::Image(ref image) => rsx! {
+					p { class: "text-xs text-slate-400 italic", "🖼 Image · {image.width}×{image.height}" }
+				},
+				ClipboardContent::Empty => rsx! {
+					p { class: "text-xs text-slate-600 italic", "Empty" }
+				},
+			}
+		}
+	}
 }
 
-/// window config for quickpaste window
-pub fn quick_paste_config() -> Config {
+/// where the popup should open for its placement setting, in physical pixels
+/// read before the popup takes focus, `None` leaves it to the os (wayland doesn't let windows pick)
+pub fn popup_position(window: &Window, popup: &PopupSettings) -> Option<PhysicalPosition<f64>> {
+	let mouse = window.cursor_position().ok().map(|position| Rect::point(position.x, position.y));
+	let anchor = match popup.placement {
+		PopupPlacement::Caret => placement::caret().or(mouse),
+		PopupPlacement::Mouse => mouse,
+		PopupPlacement::Center => None,
+	};
+	// centered popups go on the monitor with the mouse
+	let monitor = anchor
+		.or(mouse)
+		.and_then(|point| window.monitor_from_point(point.x, point.y))
+		.or_else(|| window.primary_monitor())?;
+	let (position, size) = (monitor.position(), monitor.size());
+	let bounds = Rect { x: f64::from(position.x), y: f64::from(position.y), width: f64::from(size.width), height: f64::from(size.height) };
+	let scale = monitor.scale_factor();
+	let (x, y) = placement::place(anchor, popup.width * scale, popup.height * scale, placement::work_area(bounds));
+	Some(PhysicalPosition::new(x, y))
+}
+
+/// window config for the quickpaste window, created hidden
+/// size and position are set again each time it's shown
+pub fn quick_paste_config(popup: &PopupSettings) -> Config {
 	Config::new()
 		.with_window(
 			WindowBuilder::new()
@@ -144,12 +455,12 @@ pub fn quick_paste_config() -> Config {
 				.with_decorations(false)
 				.with_transparent(true)
 				.with_resizable(false)
+				.with_visible(false)
 				// so there's this issue where if the focused state is not set to false, the first instance of focus is not registered and the
 				// user has to unfocus twice before the unfocus event is triggered. not sure if it's a bug or not, but this fixes it temporarily
 				.with_focused(false)
-				.with_inner_size(dioxus::desktop::tao::dpi::LogicalSize::new(360.0_f64, 480.0_f64))
+				.with_inner_size(dioxus::desktop::tao::dpi::LogicalSize::new(popup.width, popup.height))
 				.with_always_on_top(true)
 		)
 		.with_close_behaviour(dioxus::desktop::WindowCloseBehaviour::WindowHides)
 }
-
@@ -0,0 +1,47 @@
+use chrono::{DateTime, Duration, Local};
+
+/// how often the background task prunes and considers vacuuming
+pub const PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30 * 60);
+
+/// vacuum once this fraction of database pages is free
+pub const VACUUM_FREE_RATIO: f64 = 0.25;
+
+/// limits on stored history, enforced oldest first, `None` disables a rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetentionPolicy {
+	/// keep at most this many entries
+	pub max_entries: Option<usize>,
+	/// drop entries copied more than this many days ago
+	pub max_age_days: Option<u32>,
+	/// cap on the total stored size of all entries
+	pub max_size_mb: Option<u64>,
+	/// keep at most this many images
+	pub max_images: Option<usize>,
+	/// drop images copied more than this many days ago
+	pub max_image_age_days: Option<u32>,
+	/// cap on the total stored size of images
+	pub max_image_size_mb: Option<u64>,
+}
+
+impl Default for RetentionPolicy {
+	fn default() -> Self {
+		Self {
+			max_entries: Some(10_000),
+			max_age_days: None,
+			max_size_mb: Some(1024),
+			max_images: Some(1_000),
+			max_image_age_days: None,
+			max_image_size_mb: Some(512),
+		}
+	}
+}
+
+/// cutoff timestamp for an age rule, entries copied before it are dropped
+pub fn age_cutoff(now: DateTime<Local>, days: Option<u32>) -> Option<String> {
+	days.map(|days| (now - Duration::days(i64::from(days))).to_rfc3339())
+}
+
+/// byte count for a size rule
+pub fn megabytes(mb: Option<u64>) -> Option<i64> {
+	mb.map(|mb| (mb * 1024 * 1024) as i64)
+}
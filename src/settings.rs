@@ -0,0 +1,472 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use crate::hotkeys::{self, HotkeyAction};
+use crate::retention::RetentionPolicy;
+use crate::sensitive::{SensitiveAction, SensitivePolicy};
+
+/// how often the settings file is checked for edits made outside the app
+pub const RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
+/// `settings.toml` next to the database
+pub fn settings_path() -> PathBuf {
+	crate::db::data_dir().join("settings.toml")
+}
+
+/// everything configurable, sections map to tables in `settings.toml`
+/// missing keys keep their defaults, unknown ones are an error so typos don't go unnoticed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+	pub hotkeys: HotkeySettings,
+	pub search: SearchSettings,
+	pub popup: PopupSettings,
+	pub paste: PasteSettings,
+	pub capture: CaptureSettings,
+	pub retention: RetentionSettings,
+	pub sensitive: SensitiveSettings,
+}
+
+/// global shortcuts, in the `Ctrl+Shift+KeyV` form, empty leaves an action unbound
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HotkeySettings {
+	pub open_main_window: String,
+	pub quick_paste: String,
+	pub paste_previous: String,
+	pub pause_capture: String,
+	pub paste_plain_text: String,
+}
+
+impl Default for HotkeySettings {
+	fn default() -> Self {
+		Self {
+			open_main_window: String::new(),
+			quick_paste: "Ctrl+Shift+KeyV".to_string(),
+			paste_previous: String::new(),
+			pause_capture: "Ctrl+Shift+Alt+KeyP".to_string(),
+			paste_plain_text: String::new(),
+		}
+	}
+}
+
+impl HotkeySettings {
+	pub fn binding(&self, action: HotkeyAction) -> &str {
+		match action {
+			HotkeyAction::OpenMainWindow => &self.open_main_window,
+			HotkeyAction::QuickPaste => &self.quick_paste,
+			HotkeyAction::PastePrevious => &self.paste_previous,
+			HotkeyAction::TogglePause => &self.pause_capture,
+			HotkeyAction::PastePlainText => &self.paste_plain_text,
+		}
+	}
+
+	pub fn binding_mut(&mut self, action: HotkeyAction) -> &mut String {
+		match action {
+			HotkeyAction::OpenMainWindow => &mut self.open_main_window,
+			HotkeyAction::QuickPaste => &mut self.quick_paste,
+			HotkeyAction::PastePrevious => &mut self.paste_previous,
+			HotkeyAction::TogglePause => &mut self.pause_capture,
+			HotkeyAction::PastePlainText => &mut self.paste_plain_text,
+		}
+	}
+
+	/// every action with its accelerator, bound or not
+	pub fn bindings(&self) -> impl Iterator<Item = (HotkeyAction, &str)> {
+		HotkeyAction::ALL.into_iter().map(|action| (action, self.binding(action)))
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchSettings {
+	/// wait for typing to settle this long before embedding the query
+	pub debounce_ms: u64,
+	/// image similarities are scaled up by this, they run much lower than text ones
+	pub image_similarity_weight: f32,
+	/// applies on the next start
+	pub model: SearchModel,
+}
+
+impl Default for SearchSettings {
+	fn default() -> Self {
+		Self { debounce_ms: 350, image_similarity_weight: 10.0, model: SearchModel::default() }
+	}
+}
+
+/// text and image models that embed into the same space, so text queries find images
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SearchModel {
+	#[default]
+	NomicV15,
+	/// quantized text model, smaller and faster at some cost in quality
+	NomicV15Quantized,
+	ClipVitB32,
+}
+
+impl SearchModel {
+	pub const ALL: [SearchModel; 3] = [SearchModel::NomicV15, SearchModel::NomicV15Quantized, SearchModel::ClipVitB32];
+
+	pub fn label(self) -> &'static str {
+		match self {
+			SearchModel::NomicV15 => "Nomic v1.5",
+			SearchModel::NomicV15Quantized => "Nomic v1.5 (quantized)",
+			SearchModel::ClipVitB32 => "CLIP ViT-B/32",
+		}
+	}
+}
+
+/// size of the quick-paste popup in logical pixels, and where it opens
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PopupSettings {
+	pub width: f64,
+	pub height: f64,
+	pub placement: PopupPlacement,
+}
+
+impl Default for PopupSettings {
+	fn default() -> Self {
+		Self { width: 360.0, height: 480.0, placement: PopupPlacement::default() }
+	}
+}
+
+/// where the quick-paste popup opens, always inside the work area of the monitor it's on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PopupPlacement {
+	/// below the text caret, or the mouse when the focused app doesn't report one
+	#[default]
+	Caret,
+	Mouse,
+	/// the middle of the monitor with the mouse
+	Center,
+}
+
+impl PopupPlacement {
+	pub const ALL: [PopupPlacement; 3] = [PopupPlacement::Caret, PopupPlacement::Mouse, PopupPlacement::Center];
+
+	pub fn label(self) -> &'static str {
+		match self {
+			PopupPlacement::Caret => "At the text caret",
+			PopupPlacement::Mouse => "At the mouse",
+			PopupPlacement::Center => "Centered",
+		}
+	}
+}
+
+/// what happens after an entry is picked in the quick-paste popup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasteSettings {
+	/// focus the window the popup opened over and paste into it, rather than only copying
+	pub auto_paste: bool,
+	/// apps pasted into with ctrl+shift+v, case-insensitive globs over the process name or window class
+	pub shift_paste_apps: Vec<String>,
+}
+
+impl Default for PasteSettings {
+	fn default() -> Self {
+		let terminals = ["*term*", "konsole", "alacritty", "kitty", "foot", "tilix", "urxvt"];
+		Self { auto_paste: true, shift_paste_apps: terminals.map(String::from).to_vec() }
+	}
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CaptureSettings {
+	/// record the PRIMARY selection too, only on linux
+	pub record_selections: bool,
+}
+
+/// `RetentionPolicy` with 0 for no limit, toml has no way to leave a value out on purpose
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionSettings {
+	pub max_entries: usize,
+	pub max_age_days: u32,
+	pub max_size_mb: u64,
+	pub max_images: usize,
+	pub max_image_age_days: u32,
+	pub max_image_size_mb: u64,
+}
+
+impl Default for RetentionSettings {
+	fn default() -> Self {
+		let policy = RetentionPolicy::default();
+		Self {
+			max_entries: policy.max_entries.unwrap_or(0),
+			max_age_days: policy.max_age_days.unwrap_or(0),
+			max_size_mb: policy.max_size_mb.unwrap_or(0),
+			max_images: policy.max_images.unwrap_or(0),
+			max_image_age_days: policy.max_image_age_days.unwrap_or(0),
+			max_image_size_mb: policy.max_image_size_mb.unwrap_or(0),
+		}
+	}
+}
+
+impl RetentionSettings {
+	pub fn policy(&self) -> RetentionPolicy {
+		RetentionPolicy {
+			max_entries: (self.max_entries > 0).then_some(self.max_entries),
+			max_age_days: (self.max_age_days > 0).then_some(self.max_age_days),
+			max_size_mb: (self.max_size_mb > 0).then_some(self.max_size_mb),
+			max_images: (self.max_images > 0).then_some(self.max_images),
+			max_image_age_days: (self.max_image_age_days > 0).then_some(self.max_image_age_days),
+			max_image_size_mb: (self.max_image_size_mb > 0).then_some(self.max_image_size_mb),
+		}
+	}
+}
+
+/// what to do with each kind of secret, see `RuleAction`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SensitiveSettings {
+	pub aws_keys: RuleAction,
+	pub github_tokens: RuleAction,
+	pub slack_tokens: RuleAction,
+	pub private_keys: RuleAction,
+	pub jwts: RuleAction,
+	pub card_numbers: RuleAction,
+	pub high_entropy: RuleAction,
+	pub concealed: RuleAction,
+}
+
+impl Default for SensitiveSettings {
+	fn default() -> Self {
+		let policy = SensitivePolicy::default();
+		Self {
+			aws_keys: RuleAction(policy.aws_keys),
+			github_tokens: RuleAction(policy.github_tokens),
+			slack_tokens: RuleAction(policy.slack_tokens),
+			private_keys: RuleAction(policy.private_keys),
+			jwts: RuleAction(policy.jwts),
+			card_numbers: RuleAction(policy.card_numbers),
+			high_entropy: RuleAction(policy.high_entropy),
+			concealed: RuleAction(policy.concealed),
+		}
+	}
+}
+
+impl SensitiveSettings {
+	pub fn policy(&self) -> SensitivePolicy {
+		SensitivePolicy {
+			aws_keys: self.aws_keys.0,
+			github_tokens: self.github_tokens.0,
+			slack_tokens: self.slack_tokens.0,
+			private_keys: self.private_keys.0,
+			jwts: self.jwts.0,
+			card_numbers: self.card_numbers.0,
+			high_entropy: self.high_entropy.0,
+			concealed: self.concealed.0,
+		}
+	}
+}
+
+/// a sensitive rule's action, written `"off"`, `"skip"`, `"mask"` or `"expire:<minutes>"`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RuleAction(pub Option<SensitiveAction>);
+
+impl FromStr for RuleAction {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let action = match s.trim() {
+			"off" => None,
+			"skip" => Some(SensitiveAction::Skip),
+			"mask" => Some(SensitiveAction::Mask),
+			other => {
+				let minutes = other
+					.strip_prefix("expire:")
+					.and_then(|minutes| minutes.trim().parse::<u32>().ok())
+					.filter(|&minutes| minutes > 0)
+					.ok_or_else(|| format!("`{other}` isn't one of \"off\", \"skip\", \"mask\" or \"expire:<minutes>\""))?;
+				Some(SensitiveAction::Expire { minutes })
+			}
+		};
+		Ok(Self(action))
+	}
+}
+
+impl TryFrom<String> for RuleAction {
+	type Error = String;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		value.parse()
+	}
+}
+
+impl From<RuleAction> for String {
+	fn from(action: RuleAction) -> Self {
+		match action.0 {
+			None => "off".to_string(),
+			Some(SensitiveAction::Skip) => "skip".to_string(),
+			Some(SensitiveAction::Mask) => "mask".to_string(),
+			Some(SensitiveAction::Expire { minutes }) => format!("expire:{minutes}"),
+		}
+	}
+}
+
+impl Settings {
+	/// parse and validate the contents of a settings file
+	pub fn parse(text: &str) -> anyhow::Result<Self> {
+		let mut settings: Settings = toml::from_str(text)?;
+		// hand-edited lists pick up stray spaces and empty items
+		settings.paste.shift_paste_apps = settings.paste.shift_paste_apps.iter().map(|app| app.trim()).filter(|app| !app.is_empty()).map(str::to_string).collect();
+		settings.validate()?;
+		Ok(settings)
+	}
+
+	/// every problem at once, one per line, so a bad edit can be fixed in one go
+	pub fn validate(&self) -> anyhow::Result<()> {
+		let mut problems = Vec::new();
+		let (_, hotkey_problems) = hotkeys::resolve(self.hotkeys.bindings());
+		problems.extend(hotkey_problems.into_iter().map(|problem| format!("hotkeys: {problem}")));
+		if self.search.debounce_ms > 5_000 {
+			problems.push(format!("search.debounce_ms: {} is over the 5000 ms maximum", self.search.debounce_ms));
+		}
+		if !(self.search.image_similarity_weight.is_finite() && self.search.image_similarity_weight > 0.0) {
+			problems.push(format!("search.image_similarity_weight: {} must be a positive number", self.search.image_similarity_weight));
+		}
+		for (key, size) in [("popup.width", self.popup.width), ("popup.height", self.popup.height)] {
+			if !(200.0..=4000.0).contains(&size) {
+				problems.push(format!("{key}: {size} must be between 200 and 4000"));
+			}
+		}
+		if problems.is_empty() {
+			Ok(())
+		} else {
+			Err(anyhow::anyhow!(problems.join("\n")))
+		}
+	}
+
+	/// the settings at `path`, written out with defaults if there's no file yet
+	pub fn load_or_create(path: &Path) -> anyhow::Result<Self> {
+		match std::fs::read_to_string(path) {
+			Ok(text) => Self::parse(&text).with_context(|| format!("Invalid settings in {}", path.display())),
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+				let settings = Self::default();
+				settings.save(path)?;
+				Ok(settings)
+			}
+			Err(err) => Err(err).with_context(|| format!("Couldn't read {}", path.display())),
+		}
+	}
+
+	/// validate and write, through a temporary file so a crash can't leave half a config behind
+	pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+		self.validate()?;
+		if let Some(parent) = path.parent() {
+			std::fs::create_dir_all(parent)?;
+		}
+		let temp = path.with_extension("toml.tmp");
+		std::fs::write(&temp, toml::to_string_pretty(self)?)?;
+		std::fs::rename(&temp, path).with_context(|| format!("Couldn't write {}", path.display()))
+	}
+}
+
+/// notices when the settings file was modified since the last check
+pub struct SettingsWatcher {
+	path: PathBuf,
+	modified: Option<SystemTime>,
+}
+
+impl SettingsWatcher {
+	pub fn new(path: PathBuf) -> Self {
+		let modified = modified_at(&path);
+		Self { path, modified }
+	}
+
+	pub fn changed(&mut self) -> bool {
+		let modified = modified_at(&self.path);
+		let changed = modified != self.modified;
+		self.modified = modified;
+		changed
+	}
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+	std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn defaults_round_trip_through_toml() {
+		let settings = Settings::default();
+		let text = toml::to_string_pretty(&settings).unwrap();
+		assert_eq!(Settings::parse(&text).unwrap(), settings);
+		assert_eq!(settings.retention.policy(), RetentionPolicy::default());
+		assert_eq!(settings.sensitive.policy(), SensitivePolicy::default());
+	}
+
+	#[test]
+	fn missing_keys_keep_their_defaults() {
+		let settings = Settings::parse("[search]\ndebounce_ms = 100\n\n[retention]\nmax_entries = 0\n").unwrap();
+		assert_eq!(settings.search.debounce_ms, 100);
+		assert_eq!(settings.search.image_similarity_weight, 10.0);
+		assert_eq!(settings.retention.policy().max_entries, None);
+		assert_eq!(settings.hotkeys, HotkeySettings::default());
+		assert_eq!(settings.paste, PasteSettings::default());
+	}
+
+	#[test]
+	fn shift_paste_apps_are_trimmed() {
+		let settings = Settings::parse("[paste]\nshift_paste_apps = [\" kitty \", \"\", \"*term*\"]\n").unwrap();
+		assert_eq!(settings.paste.shift_paste_apps, ["kitty", "*term*"]);
+	}
+
+	#[test]
+	fn popup_placement_is_written_in_kebab_case() {
+		let settings = Settings::parse("[popup]\nplacement = \"center\"\n").unwrap();
+		assert_eq!(settings.popup.placement, PopupPlacement::Center);
+		assert!(toml::to_string(&PopupSettings::default()).unwrap().contains("placement = \"caret\""));
+		assert!(Settings::parse("[popup]\nplacement = \"cursor\"\n").is_err());
+	}
+
+	#[test]
+	fn rule_actions_are_written_as_strings() {
+		let settings = Settings::parse("[sensitive]\njwts = \"off\"\nhigh_entropy = \"expire:5\"\naws_keys = \"skip\"\n").unwrap();
+		let policy = settings.sensitive.policy();
+		assert_eq!(policy.jwts, None);
+		assert_eq!(policy.high_entropy, Some(SensitiveAction::Expire { minutes: 5 }));
+		assert_eq!(policy.aws_keys, Some(SensitiveAction::Skip));
+		assert!(Settings::parse("[sensitive]\njwts = \"expire:0\"\n").is_err());
+	}
+
+	#[test]
+	fn invalid_settings_name_every_problem() {
+		let err = Settings::parse("[hotkeys]\nquick_paste = \"Ctrl+Nope\"\n\n[popup]\nwidth = 10\n").unwrap_err().to_string();
+		assert!(err.contains("hotkeys: Quick paste"), "{err}");
+		assert!(err.contains("popup.width"), "{err}");
+
+		let err = Settings::parse("[search]\ndebounce = 100\n").unwrap_err().to_string();
+		assert!(err.contains("unknown field `debounce`"), "{err}");
+	}
+
+	#[test]
+	fn hotkeys_bound_twice_are_rejected() {
+		let err = Settings::parse("[hotkeys]\npaste_plain_text = \"Ctrl+Shift+KeyV\"\n").unwrap_err().to_string();
+		assert!(err.contains("already bound to Quick paste"), "{err}");
+	}
+
+	#[test]
+	fn missing_files_are_created_with_defaults() {
+		let dir = std::env::temp_dir().join(format!("shadowpaste-settings-{}", std::process::id()));
+		let path = dir.join("settings.toml");
+		let _ = std::fs::remove_file(&path);
+
+		assert_eq!(Settings::load_or_create(&path).unwrap(), Settings::default());
+		let mut watcher = SettingsWatcher::new(path.clone());
+		assert!(!watcher.changed());
+		std::fs::remove_file(&path).unwrap();
+		assert!(watcher.changed());
+		let _ = std::fs::remove_dir(&dir);
+	}
+}
@@ -0,0 +1,272 @@
+use dioxus::prelude::*;
+use crate::hotkeys::HotkeyAction;
+use crate::sensitive::SensitiveAction;
+use crate::settings::{PopupPlacement, RuleAction, SearchModel, Settings};
+
+const INPUT: &str = "w-40 px-2 py-1 bg-slate-900 border border-slate-800 rounded-md text-sm text-slate-200 focus:outline-none focus:ring-1 focus:ring-blue-500/50 focus:border-blue-500/50";
+
+/// edits a copy of the settings, nothing applies until saved
+#[component]
+pub fn SettingsView(
+	settings: Settings,
+	/// why the last save or reload failed
+	error: Option<String>,
+	/// shortcuts that are saved but couldn't be registered
+	hotkey_errors: Vec<String>,
+	/// where the settings live, edits made there apply too
+	path: String,
+	on_save: EventHandler<Settings>,
+	on_close: EventHandler<()>,
+) -> Element {
+	let mut draft = use_signal(|| settings.clone());
+	// the file changed underneath us, start over from what's in it
+	use_effect(use_reactive!(|settings| draft.set(settings)));
+
+	let current = draft();
+	let retention = current.retention.clone();
+	let sensitive = current.sensitive.clone();
+	let shift_paste_apps = current.paste.shift_paste_apps.join(", ");
+
+	rsx! {
+		div { class: "flex-1 flex flex-col overflow-hidden",
+			div { class: "flex items-center justify-between px-4 py-3 border-b border-slate-800 shrink-0",
+				h2 { class: "text-base font-semibold text-slate-200", "Settings" }
+				div { class: "flex gap-2",
+					button {
+						class: "px-3 py-1.5 text-sm text-slate-300 bg-slate-800 hover:bg-slate-700 rounded-md transition-colors",
+						onclick: move |_| on_close.call(()),
+						"Back"
+					}
+					button {
+						class: "px-3 py-1.5 text-sm text-white bg-blue-500/80 hover:bg-blue-500 rounded-md transition-colors disabled:opacity-40",
+						disabled: draft() == settings,
+						onclick: move |_| on_save.call(draft()),
+						"Save"
+					}
+				}
+			}
+
+			if let Some(error) = error {
+				pre { class: "mx-4 mt-3 px-3 py-2 rounded-lg bg-red-500/10 border border-red-500/20 text-xs text-red-300 whitespace-pre-wrap shrink-0", "{error}" }
+			}
+
+			div { class: "flex-1 overflow-y-auto px-4 py-3 space-y-5",
+				Section { title: "Hotkeys", hint: "e.g. Ctrl+Shift+KeyV, empty to leave unbound",
+					for action in HotkeyAction::ALL {
+						Field { key: "{action:?}", label: action.label(),
+							input { class: INPUT, placeholder: "not bound", value: "{current.hotkeys.binding(action)}",
+								oninput: move |e| *draft.write().hotkeys.binding_mut(action) = e.value()
+							}
+						}
+					}
+					for error in hotkey_errors {
+						p { class: "text-xs text-red-300", "{error}" }
+					}
+				}
+
+				Section { title: "Search",
+					Field { label: "Debounce (ms)",
+						input { class: INPUT, r#type: "number", min: "0", value: "{current.search.debounce_ms}",
+							oninput: move |e| if let Ok(value) = e.value().parse() { draft.write().search.debounce_ms = value }
+						}
+					}
+					Field { label: "Image similarity weight",
+						input { class: INPUT, r#type: "number", step: "0.5", value: "{current.search.image_similarity_weight}",
+							oninput: move |e| if let Ok(value) = e.value().parse() { draft.write().search.image_similarity_weight = value }
+						}
+					}
+					Field { label: "Model", hint: "applies after a restart, older entries are only found by text until copied again",
+						select { class: INPUT,
+							onchange: move |e| {
+								if let Some(model) = SearchModel::ALL.into_iter().find(|model| model.label() == e.value()) {
+									draft.write().search.model = model;
+								}
+							},
+							for model in SearchModel::ALL {
+								option { value: model.label(), selected: model == current.search.model, "{model.label()}" }
+							}
+						}
+					}
+				}
+
+				Section { title: "Quick paste popup",
+					Field { label: "Width",
+						input { class: INPUT, r#type: "number", value: "{current.popup.width}",
+							oninput: move |e| if let Ok(value) = e.value().parse() { draft.write().popup.width = value }
+						}
+					}
+					Field { label: "Height",
+						input { class: INPUT, r#type: "number", value: "{current.popup.height}",
+							oninput: move |e| if let Ok(value) = e.value().parse() { draft.write().popup.height = value }
+						}
+					}
+					Field { label: "Opens", hint: "the caret is only known on Windows, elsewhere it opens at the mouse",
+						select { class: INPUT,
+							onchange: move |e| {
+								if let Some(placement) = PopupPlacement::ALL.into_iter().find(|placement| placement.label() == e.value()) {
+									draft.write().popup.placement = placement;
+								}
+							},
+							for placement in PopupPlacement::ALL {
+								option { value: placement.label(), selected: placement == current.popup.placement, "{placement.label()}" }
+							}
+						}
+					}
+				}
+
+				Section { title: "Paste",
+					Field { label: "Paste into the previous window", hint: "X11 and Windows, elsewhere entries are only copied",
+						input { r#type: "checkbox", checked: current.paste.auto_paste,
+							onchange: move |_| {
+								let mut draft = draft.write();
+								draft.paste.auto_paste = !draft.paste.auto_paste;
+							}
+						}
+					}
+					Field { label: "Ctrl+Shift+V apps", hint: "comma-separated globs, for terminals",
+						input { class: INPUT, value: "{shift_paste_apps}",
+							oninput: move |e| draft.write().paste.shift_paste_apps = e.value().split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect()
+						}
+					}
+				}
+
+				if cfg!(all(unix, not(target_os = "macos"))) {
+					Section { title: "Capture",
+						Field { label: "Record selections",
+							input { r#type: "checkbox", checked: current.capture.record_selections,
+								onchange: move |_| {
+									let mut draft = draft.write();
+									draft.capture.record_selections = !draft.capture.record_selections;
+								}
+							}
+						}
+					}
+				}
+
+				Section { title: "Retention", hint: "0 means no limit",
+					Field { label: "Entries",
+						input { class: INPUT, r#type: "number", min: "0", value: "{retention.max_entries}",
+							oninput: move |e| if let Ok(value) = e.value().parse() { draft.write().retention.max_entries = value }
+						}
+					}
+					Field { label: "Age (days)",
+						input { class: INPUT, r#type: "number", min: "0", value: "{retention.max_age_days}",
+							oninput: move |e| if let Ok(value) = e.value().parse() { draft.write().retention.max_age_days = value }
+						}
+					}
+					Field { label: "Total size (MB)",
+						input { class: INPUT, r#type: "number", min: "0", value: "{retention.max_size_mb}",
+							oninput: move |e| if let Ok(value) = e.value().parse() { draft.write().retention.max_size_mb = value }
+						}
+					}
+					Field { label: "Images",
+						input { class: INPUT, r#type: "number", min: "0", value: "{retention.max_images}",
+							oninput: move |e| if let Ok(value) = e.value().parse() { draft.write().retention.max_images = value }
+						}
+					}
+					Field { label: "Image age (days)",
+						input { class: INPUT, r#type: "number", min: "0", value: "{retention.max_image_age_days}",
+							oninput: move |e| if let Ok(value) = e.value().parse() { draft.write().retention.max_image_age_days = value }
+						}
+					}
+					Field { label: "Image size (MB)",
+						input { class: INPUT, r#type: "number", min: "0", value: "{retention.max_image_size_mb}",
+							oninput: move |e| if let Ok(value) = e.value().parse() { draft.write().retention.max_image_size_mb = value }
+						}
+					}
+				}
+
+				Section { title: "Secrets",
+					RuleField { label: "AWS keys", action: sensitive.aws_keys, on_change: move |action| draft.write().sensitive.aws_keys = action }
+					RuleField { label: "GitHub tokens", action: sensitive.github_tokens, on_change: move |action| draft.write().sensitive.github_tokens = action }
+					RuleField { label: "Slack tokens", action: sensitive.slack_tokens, on_change: move |action| draft.write().sensitive.slack_tokens = action }
+					RuleField { label: "Private keys", action: sensitive.private_keys, on_change: move |action| draft.write().sensitive.private_keys = action }
+					RuleField { label: "JWTs", action: sensitive.jwts, on_change: move |action| draft.write().sensitive.jwts = action }
+					RuleField { label: "Card numbers", action: sensitive.card_numbers, on_change: move |action| draft.write().sensitive.card_numbers = action }
+					RuleField { label: "High-entropy strings", action: sensitive.high_entropy, on_change: move |action| draft.write().sensitive.high_entropy = action }
+					RuleField { label: "Passwords", action: sensitive.concealed, on_change: move |action| draft.write().sensitive.concealed = action }
+				}
+
+				p { class: "text-xs text-slate-500 pb-2", "Stored in {path}, edits to the file apply right away." }
+			}
+		}
+	}
+}
+
+#[component]
+fn Section(title: &'static str, hint: Option<&'static str>, children: Element) -> Element {
+	rsx! {
+		div { class: "space-y-2",
+			div { class: "flex items-baseline gap-2",
+				h3 { class: "text-xs font-semibold uppercase tracking-wide text-slate-400", "{title}" }
+				if let Some(hint) = hint {
+					span { class: "text-xs text-slate-600", "{hint}" }
+				}
+			}
+			{children}
+		}
+	}
+}
+
+#[component]
+fn Field(label: &'static str, hint: Option<&'static str>, children: Element) -> Element {
+	rsx! {
+		label { class: "flex items-center justify-between gap-4 text-sm text-slate-300",
+			div { class: "flex flex-col",
+				"{label}"
+				if let Some(hint) = hint {
+					span { class: "text-xs text-slate-500", "{hint}" }
+				}
+			}
+			{children}
+		}
+	}
+}
+
+/// off, skip, mask or expire after a number of minutes
+#[component]
+fn RuleField(label: &'static str, action: RuleAction, on_change: EventHandler<RuleAction>) -> Element {
+	let kind = match action.0 {
+		None => "off",
+		Some(SensitiveAction::Skip) => "skip",
+		Some(SensitiveAction::Mask) => "mask",
+		Some(SensitiveAction::Expire { .. }) => "expire",
+	};
+	let minutes = match action.0 {
+		Some(SensitiveAction::Expire { minutes }) => Some(minutes),
+		_ => None,
+	};
+
+	rsx! {
+		Field { label,
+			div { class: "flex items-center gap-2",
+				if let Some(minutes) = minutes {
+					input { class: "w-16 px-2 py-1 bg-slate-900 border border-slate-800 rounded-md text-sm text-slate-200", r#type: "number", min: "1", value: "{minutes}",
+						oninput: move |e| {
+							if let Ok(minutes) = e.value().parse::<u32>() {
+								if minutes > 0 {
+									on_change.call(RuleAction(Some(SensitiveAction::Expire { minutes })));
+								}
+							}
+						}
+					}
+					span { class: "text-xs text-slate-500", "min" }
+				}
+				select { class: "w-24 px-2 py-1 bg-slate-900 border border-slate-800 rounded-md text-sm text-slate-200",
+					onchange: move |e| {
+						let action = match e.value().as_str() {
+							"skip" => Some(SensitiveAction::Skip),
+							"mask" => Some(SensitiveAction::Mask),
+							"expire" => Some(SensitiveAction::Expire { minutes: minutes.unwrap_or(60) }),
+							_ => None,
+						};
+						on_change.call(RuleAction(action));
+					},
+					for choice in ["off", "skip", "mask", "expire"] {
+						option { value: choice, selected: choice == kind, "{choice}" }
+					}
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,260 @@
+use std::sync::{Arc, Mutex};
+
+/// the application a copy was made in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceApp {
+	/// process name without its extension, e.g. `firefox`, or the window class when the process isn't known
+	pub name: String,
+	/// title of the focused window, can be empty
+	pub title: String,
+	/// window class, e.g. `Navigator` or `MozillaWindowClass`, only known at capture time and empty once stored
+	pub class: String,
+}
+
+impl SourceApp {
+	/// whether any of `patterns` matches the process name or the window class
+	pub fn matches(&self, patterns: &[String]) -> bool {
+		patterns.iter().any(|pattern| glob_match(pattern, &self.name) || (!self.class.is_empty() && glob_match(pattern, &self.class)))
+	}
+}
+
+/// apps whose copies are never recorded, as case-insensitive globs over the process name or window class
+pub type IgnoredApps = Arc<Mutex<Vec<String>>>;
+
+/// `*` matches any run of characters and `?` any single one, case-insensitively
+fn glob_match(pattern: &str, text: &str) -> bool {
+	let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+	let text: Vec<char> = text.to_lowercase().chars().collect();
+
+	// backtrack to the last `*` on a mismatch, letting it swallow one more character
+	let (mut p, mut t) = (0, 0);
+	let mut star: Option<(usize, usize)> = None;
+	while t < text.len() {
+		match pattern.get(p) {
+			Some('*') => {
+				star = Some((p, t));
+				p += 1;
+			}
+			Some(&c) if c == '?' || c == text[t] => {
+				p += 1;
+				t += 1;
+			}
+			_ => match star {
+				Some((star_p, star_t)) => {
+					star = Some((star_p, star_t + 1));
+					p = star_p + 1;
+					t = star_t + 1;
+				}
+				None => return false,
+			},
+		}
+	}
+	pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// executable name without directories or extension, `C:\...\firefox.exe` -> `firefox`
+#[cfg_attr(not(windows), allow(dead_code))]
+fn process_name(path: &str) -> String {
+	let file = path.rsplit(['/', '\\']).next().unwrap_or(path);
+	match file.rsplit_once('.') {
+		Some((stem, _)) if !stem.is_empty() => stem.to_string(),
+		_ => file.to_string(),
+	}
+}
+
+/// looks up the foreground window through the win32 api
+#[cfg(windows)]
+#[derive(Default)]
+pub struct SourceAppReader;
+
+#[cfg(windows)]
+impl SourceAppReader {
+	pub fn new() -> Self {
+		Self
+	}
+
+	/// whether copies can be told apart by app at all
+	pub fn identifies_apps(&self) -> bool {
+		true
+	}
+
+	pub fn current(&self) -> Option<SourceApp> {
+		use windows_sys::Win32::Foundation::CloseHandle;
+		use windows_sys::Win32::System::Threading::{OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION};
+		use windows_sys::Win32::UI::WindowsAndMessaging::{GetClassNameW, GetForegroundWindow, GetWindowTextLengthW, GetWindowTextW, GetWindowThreadProcessId};
+
+		// SAFETY: plain win32 calls on the foreground window, buffers are sized by the lengths passed along
+		unsafe {
+			let window = GetForegroundWindow();
+			if window.is_null() {
+				return None;
+			}
+
+			let mut title = vec![0u16; GetWindowTextLengthW(window).max(0) as usize + 1];
+			let title_len = GetWindowTextW(window, title.as_mut_ptr(), title.len() as i32).max(0) as usize;
+			let title = String::from_utf16_lossy(&title[..title_len]);
+
+			// class names are at most 256 characters
+			let mut class = [0u16; 257];
+			let class_len = GetClassNameW(window, class.as_mut_ptr(), class.len() as i32).max(0) as usize;
+			let class = String::from_utf16_lossy(&class[..class_len]);
+
+			let mut pid = 0;
+			GetWindowThreadProcessId(window, &mut pid);
+			let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+			if process.is_null() {
+				return None;
+			}
+			let mut path = [0u16; 1024];
+			let mut path_len = path.len() as u32;
+			let found = QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, path.as_mut_ptr(), &mut path_len) != 0;
+			CloseHandle(process);
+			if !found {
+				return None;
+			}
+
+			Some(SourceApp { name: process_name(&String::from_utf16_lossy(&path[..path_len as usize])), title, class })
+		}
+	}
+}
+
+/// looks up `_NET_ACTIVE_WINDOW` on the X server, holds on to its connection
+/// wayland doesn't tell clients which window is focused, so there's nothing to read there
+#[cfg(all(unix, not(target_os = "macos")))]
+pub struct SourceAppReader {
+	x11: Option<(x11rb::rust_connection::RustConnection, usize)>,
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl Default for SourceAppReader {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl SourceAppReader {
+	pub fn new() -> Self {
+		// xwayland only knows about X11 windows, its active window would be stale under native wayland apps
+		let x11 = std::env::var_os("WAYLAND_DISPLAY").is_none().then(|| x11rb::connect(None).ok()).flatten();
+		Self { x11 }
+	}
+
+	/// whether copies can be told apart by app at all, never under wayland
+	pub fn identifies_apps(&self) -> bool {
+		self.x11.is_some()
+	}
+
+	pub fn current(&self) -> Option<SourceApp> {
+		use x11rb::connection::Connection;
+		use x11rb::protocol::xproto::{AtomEnum, ConnectionExt, Window};
+
+		let (connection, screen) = self.x11.as_ref()?;
+		let atom = |name: &str| Some(connection.intern_atom(false, name.as_bytes()).ok()?.reply().ok()?.atom);
+		let property = |window: Window, property: u32, kind: u32| {
+			connection.get_property(false, window, property, kind, 0, 1024).ok()?.reply().ok()
+		};
+
+		let root = connection.setup().roots[*screen].root;
+		let window = property(root, atom("_NET_ACTIVE_WINDOW")?, AtomEnum::WINDOW.into())?.value32()?.next()?;
+		if window == 0 {
+			return None;
+		}
+
+		let title = property(window, atom("_NET_WM_NAME")?, atom("UTF8_STRING")?)
+			.filter(|reply| !reply.value.is_empty())
+			.or_else(|| property(window, AtomEnum::WM_NAME.into(), AtomEnum::STRING.into()))
+			.map(|reply| String::from_utf8_lossy(&reply.value).into_owned())
+			.unwrap_or_default();
+
+		// WM_CLASS is the instance and class names, each nul-terminated
+		let wm_class = property(window, AtomEnum::WM_CLASS.into(), AtomEnum::STRING.into()).map(|reply| reply.value).unwrap_or_default();
+		let mut wm_class = wm_class.split(|&b| b == 0).map(|part| String::from_utf8_lossy(part).into_owned());
+		let instance = wm_class.next().unwrap_or_default();
+		let class = wm_class.next().unwrap_or_default();
+
+		// the process behind the window, or its instance name when it doesn't say
+		let pid = property(window, atom("_NET_WM_PID")?, AtomEnum::CARDINAL.into()).and_then(|reply| reply.value32()?.next());
+		let name = pid.and_then(pid_name).unwrap_or(instance);
+		if name.is_empty() {
+			return None;
+		}
+
+		Some(SourceApp { name, title, class })
+	}
+}
+
+/// executable name of process `pid`, comm is cut to 15 bytes so it's only used when the executable can't be read
+#[cfg(all(unix, not(target_os = "macos")))]
+fn pid_name(pid: u32) -> Option<String> {
+	let exe = std::fs::read_link(format!("/proc/{pid}/exe")).ok();
+	let name = match exe.as_deref().and_then(std::path::Path::file_name) {
+		// a replaced binary keeps running under its old name
+		Some(file) => file.to_string_lossy().trim_end_matches(" (deleted)").to_string(),
+		None => std::fs::read_to_string(format!("/proc/{pid}/comm")).ok()?.trim().to_string(),
+	};
+	(!name.is_empty()).then_some(name)
+}
+
+/// no window lookup on other platforms
+#[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+#[derive(Default)]
+pub struct SourceAppReader;
+
+#[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+impl SourceAppReader {
+	pub fn new() -> Self {
+		Self
+	}
+
+	pub fn identifies_apps(&self) -> bool {
+		false
+	}
+
+	pub fn current(&self) -> Option<SourceApp> {
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn app(name: &str, class: &str) -> SourceApp {
+		SourceApp { name: name.to_string(), title: String::new(), class: class.to_string() }
+	}
+
+	#[test]
+	fn globs_match_case_insensitively() {
+		assert!(glob_match("keepassxc", "KeePassXC"));
+		assert!(glob_match("*term*", "gnome-terminal-server"));
+		assert!(glob_match("1password?", "1Password7"));
+		assert!(glob_match("*", ""));
+		assert!(!glob_match("term*", "xterminal"));
+		assert!(!glob_match("bash", "bash5"));
+	}
+
+	#[test]
+	fn apps_match_by_name_or_class() {
+		let patterns = ["keepass*".to_string(), "org.wezfurlong.*".to_string()];
+		assert!(app("KeePassXC", "").matches(&patterns));
+		assert!(app("wezterm-gui", "org.wezfurlong.wezterm").matches(&patterns));
+		assert!(!app("firefox", "Navigator").matches(&patterns));
+		assert!(!app("firefox", "").matches(&[]));
+	}
+
+	#[test]
+	#[cfg(all(unix, not(target_os = "macos")))]
+	fn pid_names_are_not_truncated() {
+		let exe = std::env::current_exe().unwrap();
+		assert_eq!(pid_name(std::process::id()).as_deref(), exe.file_name().and_then(|file| file.to_str()));
+	}
+
+	#[test]
+	fn process_names_drop_directories_and_extensions() {
+		assert_eq!(process_name(r"C:\Program Files\Mozilla Firefox\firefox.exe"), "firefox");
+		assert_eq!(process_name("/usr/lib/firefox/firefox"), "firefox");
+		assert_eq!(process_name("KeePassXC.exe"), "KeePassXC");
+		assert_eq!(process_name(".hidden"), ".hidden");
+	}
+}
@@ -0,0 +1,196 @@
+use anyhow::{bail, Context};
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+use base64::Engine;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use quick_xml::events::Event;
+use quick_xml::{Reader, Writer};
+use crate::monitor::ClipboardContent;
+
+/// a rewrite of an entry's text applied on the way to the clipboard, the stored entry is left alone
+/// every one of them writes plain text, so each also strips formatting
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+	/// stable and unique, unlike the label
+	pub name: &'static str,
+	pub label: &'static str,
+	rewrite: fn(&str) -> anyhow::Result<String>,
+}
+
+impl PartialEq for Transform {
+	fn eq(&self, other: &Self) -> bool {
+		self.name == other.name
+	}
+}
+
+impl Transform {
+	/// `content`'s text, rewritten, fails for images and text the rewrite doesn't apply to
+	pub fn apply(&self, content: &ClipboardContent) -> anyhow::Result<ClipboardContent> {
+		let text = content.plain_text().context("only text can be transformed")?;
+		Ok(ClipboardContent::Text((self.rewrite)(&text)?))
+	}
+}
+
+pub const PLAIN_TEXT: Transform = TRANSFORMS[0];
+
+/// everything offered when pasting, in menu order
+pub const TRANSFORMS: [Transform; 16] = [
+	Transform { name: "plain-text", label: "Plain text", rewrite: |text| Ok(text.to_string()) },
+	Transform { name: "trim", label: "Trim whitespace", rewrite: |text| Ok(text.trim().to_string()) },
+	Transform { name: "upper-case", label: "UPPER CASE", rewrite: |text| Ok(text.to_uppercase()) },
+	Transform { name: "lower-case", label: "lower case", rewrite: |text| Ok(text.to_lowercase()) },
+	Transform { name: "title-case", label: "Title Case", rewrite: title_case },
+	Transform { name: "collapse-lines", label: "Collapse lines", rewrite: collapse_lines },
+	Transform { name: "json-pretty", label: "Pretty JSON", rewrite: |text| Ok(serde_json::to_string_pretty(&parse_json(text)?)?) },
+	Transform { name: "json-minify", label: "Minified JSON", rewrite: |text| Ok(serde_json::to_string(&parse_json(text)?)?) },
+	Transform { name: "xml-pretty", label: "Pretty XML", rewrite: |text| reformat_xml(text, true) },
+	Transform { name: "xml-minify", label: "Minified XML", rewrite: |text| reformat_xml(text, false) },
+	Transform { name: "url-encode", label: "URL-encode", rewrite: |text| Ok(utf8_percent_encode(text, URL_COMPONENT).to_string()) },
+	Transform { name: "url-decode", label: "URL-decode", rewrite: url_decode },
+	Transform { name: "base64-encode", label: "Base64-encode", rewrite: |text| Ok(STANDARD.encode(text)) },
+	Transform { name: "base64-decode", label: "Base64-decode", rewrite: base64_decode },
+	Transform { name: "shell-escape", label: "Escape for shell", rewrite: |text| Ok(format!("'{}'", text.replace('\'', r"'\''"))) },
+	Transform { name: "regex-escape", label: "Escape for regex", rewrite: |text| Ok(regex::escape(text)) },
+];
+
+/// everything but the characters RFC 3986 leaves unreserved
+const URL_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+/// first letter of each word upper case, the rest lower case
+fn title_case(text: &str) -> anyhow::Result<String> {
+	let mut result = String::with_capacity(text.len());
+	let mut word_start = true;
+	for c in text.chars() {
+		if word_start {
+			result.extend(c.to_uppercase());
+		} else {
+			result.extend(c.to_lowercase());
+		}
+		word_start = c.is_whitespace();
+	}
+	Ok(result)
+}
+
+/// every non-empty line trimmed and joined with single spaces
+fn collapse_lines(text: &str) -> anyhow::Result<String> {
+	Ok(text.lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>().join(" "))
+}
+
+fn parse_json(text: &str) -> anyhow::Result<serde_json::Value> {
+	serde_json::from_str(text.trim()).context("not valid JSON")
+}
+
+/// indented two spaces per level or with the whitespace between tags dropped
+fn reformat_xml(text: &str, indent: bool) -> anyhow::Result<String> {
+	let mut reader = Reader::from_str(text.trim());
+	reader.config_mut().trim_text(true);
+	let mut writer = if indent { Writer::new_with_indent(Vec::new(), b' ', 2) } else { Writer::new(Vec::new()) };
+	// without elements it's just text, which would come out unchanged
+	let mut elements = 0;
+	loop {
+		match reader.read_event().context("not valid XML")? {
+			Event::Eof => break,
+			event => {
+				if matches!(event, Event::Start(_) | Event::Empty(_)) {
+					elements += 1;
+				}
+				writer.write_event(event)?;
+			}
+		}
+	}
+	if elements == 0 {
+		bail!("not XML");
+	}
+	Ok(String::from_utf8(writer.into_inner())?)
+}
+
+fn url_decode(text: &str) -> anyhow::Result<String> {
+	Ok(percent_decode_str(text.trim()).decode_utf8().context("doesn't decode to text")?.into_owned())
+}
+
+/// any of the standard and url-safe alphabets, padded or not, line breaks are ignored
+fn base64_decode(text: &str) -> anyhow::Result<String> {
+	let compact: String = text.split_whitespace().collect();
+	if compact.is_empty() {
+		bail!("nothing to decode");
+	}
+	let bytes = [STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD]
+		.iter()
+		.find_map(|engine| engine.decode(&compact).ok())
+		.context("not valid base64")?;
+	String::from_utf8(bytes).context("decodes to binary data, not text")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn transform(name: &str, text: &str) -> anyhow::Result<String> {
+		let transform = TRANSFORMS.iter().find(|transform| transform.name == name).unwrap();
+		match transform.apply(&ClipboardContent::Text(text.to_string()))? {
+			ClipboardContent::Text(text) => Ok(text),
+			other => panic!("expected text, got {other:?}"),
+		}
+	}
+
+	#[test]
+	fn names_are_unique() {
+		let mut names: Vec<_> = TRANSFORMS.iter().map(|transform| transform.name).collect();
+		names.sort_unstable();
+		names.dedup();
+		assert_eq!(names.len(), TRANSFORMS.len());
+	}
+
+	#[test]
+	fn formatting_is_stripped() {
+		let html = ClipboardContent::Html { html: "<b>bold</b> move".to_string(), text: "bold move".to_string() };
+		assert_eq!(PLAIN_TEXT.apply(&html).unwrap(), ClipboardContent::Text("bold move".to_string()));
+		assert_eq!(TRANSFORMS[2].apply(&html).unwrap(), ClipboardContent::Text("BOLD MOVE".to_string()));
+		let image = ClipboardContent::Image(crate::monitor::ClipboardImage { png: Vec::new(), width: 1, height: 1, byte_size: 0 });
+		assert!(PLAIN_TEXT.apply(&image).is_err());
+	}
+
+	#[test]
+	fn whitespace_and_case() {
+		assert_eq!(transform("trim", "\n  padded\t \n").unwrap(), "padded");
+		assert_eq!(transform("collapse-lines", "first\r\n  second  \n\n third").unwrap(), "first second third");
+		assert_eq!(transform("upper-case", "straße").unwrap(), "STRASSE");
+		assert_eq!(transform("lower-case", "MiXeD").unwrap(), "mixed");
+		assert_eq!(transform("title-case", "hELLO  wide\nworld").unwrap(), "Hello  Wide\nWorld");
+	}
+
+	#[test]
+	fn json_is_reformatted() {
+		// keys stay in the order they were written
+		assert_eq!(transform("json-pretty", r#" {"b":[1,2],"a":null} "#).unwrap(), "{\n  \"b\": [\n    1,\n    2\n  ],\n  \"a\": null\n}");
+		assert_eq!(transform("json-minify", "{\n  \"a\": [1, 2]\n}").unwrap(), r#"{"a":[1,2]}"#);
+		assert!(transform("json-pretty", "{not json}").is_err());
+	}
+
+	#[test]
+	fn xml_is_reformatted() {
+		let xml = "<root>\n    <item id=\"1\">one</item>\n  <empty/></root>";
+		assert_eq!(transform("xml-pretty", xml).unwrap(), "<root>\n  <item id=\"1\">one</item>\n  <empty/>\n</root>");
+		assert_eq!(transform("xml-minify", xml).unwrap(), "<root><item id=\"1\">one</item><empty/></root>");
+		assert!(transform("xml-pretty", "<a><b></a>").is_err());
+		assert!(transform("xml-pretty", "just text").is_err());
+	}
+
+	#[test]
+	fn encodings_round_trip() {
+		assert_eq!(transform("url-encode", "a b&c=d/é~").unwrap(), "a%20b%26c%3Dd%2F%C3%A9~");
+		assert_eq!(transform("url-decode", "a%20b%26c%3Dd%2F%C3%A9~").unwrap(), "a b&c=d/é~");
+		assert!(transform("url-decode", "%FF").is_err());
+
+		assert_eq!(transform("base64-encode", "hello?").unwrap(), "aGVsbG8/");
+		assert_eq!(transform("base64-decode", "aGVs\nbG8/").unwrap(), "hello?");
+		assert_eq!(transform("base64-decode", "aGVsbG8_").unwrap(), "hello?");
+		assert!(transform("base64-decode", "not base64!").is_err());
+		assert!(transform("base64-decode", "/w==").is_err());
+	}
+
+	#[test]
+	fn escaping() {
+		assert_eq!(transform("shell-escape", "it's $HOME").unwrap(), r"'it'\''s $HOME'");
+		assert_eq!(transform("regex-escape", "1+1=2 (maybe)?").unwrap(), r"1\+1=2 \(maybe\)\?");
+	}
+}
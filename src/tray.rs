@@ -0,0 +1,146 @@
+use chrono::Duration;
+use dioxus::desktop::trayicon::menu::{Menu, MenuItem, PredefinedMenuItem, Submenu};
+use dioxus::desktop::trayicon::Icon;
+use crate::db::ClipboardEntry;
+use crate::monitor::{CaptureState, ClipboardContent};
+
+/// auto-resume choices offered in the pause submenu
+pub const PAUSE_MINUTES: [i64; 3] = [5, 15, 60];
+/// how many of the newest entries the menu lists
+pub const RECENT_ENTRIES: usize = 10;
+/// longest entry label in characters, menus don't wrap
+const LABEL_CHARS: usize = 40;
+
+const QUIT: &str = "quit";
+const OPEN: &str = "open";
+const SETTINGS: &str = "settings";
+const CLEAR_HISTORY: &str = "clear-history";
+const RESUME: &str = "resume";
+const PAUSE: &str = "pause";
+const PAUSE_FOR: &str = "pause-for-";
+const COPY: &str = "copy-";
+
+/// what a tray menu item asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayAction {
+	/// put the entry with this id back on the clipboard
+	Copy(i64),
+	/// show the main window
+	Open,
+	/// stop recording, resuming by itself after the given time if there is one
+	Pause(Option<Duration>),
+	Resume,
+	/// ask to delete everything that isn't pinned
+	ClearHistory,
+	Settings,
+	Quit,
+}
+
+impl TrayAction {
+	/// the action behind a menu item id, `None` for items we didn't build
+	pub fn from_id(id: &str) -> Option<Self> {
+		match id {
+			QUIT => Some(Self::Quit),
+			OPEN => Some(Self::Open),
+			SETTINGS => Some(Self::Settings),
+			CLEAR_HISTORY => Some(Self::ClearHistory),
+			RESUME => Some(Self::Resume),
+			PAUSE => Some(Self::Pause(None)),
+			_ => {
+				if let Some(minutes) = id.strip_prefix(PAUSE_FOR) {
+					return minutes.parse().ok().map(|minutes| Self::Pause(Some(Duration::minutes(minutes))));
+				}
+				id.strip_prefix(COPY)?.parse().ok().map(Self::Copy)
+			}
+		}
+	}
+}
+
+/// the tray menu for the capture state and the newest entries
+/// menus can't be updated in place so it's rebuilt whenever either changes
+pub fn menu(state: CaptureState, recent: &[ClipboardEntry]) -> Menu {
+	let menu = Menu::new();
+	let mut result = Ok(());
+	for entry in recent.iter().take(RECENT_ENTRIES) {
+		result = result.and_then(|_| menu.append(&MenuItem::with_id(format!("{COPY}{}", entry.id), entry_label(&entry.content), true, None)));
+	}
+	if recent.is_empty() {
+		result = result.and_then(|_| menu.append(&MenuItem::new("No history yet", false, None)));
+	}
+	let result = result
+		.and_then(|_| menu.append(&PredefinedMenuItem::separator()))
+		.and_then(|_| menu.append(&MenuItem::with_id(OPEN, "Open", true, None)));
+	let result = result.and_then(|_| match state {
+		CaptureState::Recording => {
+			let pause = Submenu::new("Pause capture", true);
+			let _ = pause.append(&MenuItem::with_id(PAUSE, "Until resumed", true, None));
+			for minutes in PAUSE_MINUTES {
+				let _ = pause.append(&MenuItem::with_id(format!("{PAUSE_FOR}{minutes}"), format!("For {minutes} minutes"), true, None));
+			}
+			menu.append(&pause)
+		}
+		CaptureState::Paused { .. } => menu.append(&MenuItem::with_id(RESUME, "Resume capture", true, None)),
+	});
+	let result = result
+		.and_then(|_| menu.append(&MenuItem::with_id(CLEAR_HISTORY, "Clear history...", true, None)))
+		.and_then(|_| menu.append(&MenuItem::with_id(SETTINGS, "Settings", true, None)))
+		.and_then(|_| menu.append(&PredefinedMenuItem::separator()))
+		.and_then(|_| menu.append(&MenuItem::with_id(QUIT, "Quit", true, None)));
+	if let Err(err) = result {
+		eprintln!("Failed to build the tray menu: {err}");
+	}
+	menu
+}
+
+/// one line of an entry's text, shortened, `&` is doubled so it isn't taken for a mnemonic
+fn entry_label(content: &ClipboardContent) -> String {
+	let text = match content {
+		ClipboardContent::Image(_) => return "Image".to_string(),
+		_ => content.plain_text().unwrap_or_default(),
+	};
+	let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+	let mut label: String = text.chars().take(LABEL_CHARS).collect();
+	if text.chars().count() > LABEL_CHARS {
+		label.push('…');
+	}
+	if label.is_empty() {
+		label.push_str("(empty)");
+	}
+	label.replace('&', "&&")
+}
+
+/// hover text, says until when capture is paused
+pub fn tooltip(state: CaptureState) -> String {
+	match state {
+		CaptureState::Recording => "Shadowpaste".to_string(),
+		CaptureState::Paused { until: None } => "Shadowpaste (paused)".to_string(),
+		CaptureState::Paused { until: Some(until) } => format!("Shadowpaste (paused until {})", until.format("%H:%M")),
+	}
+}
+
+const ICON_SIZE: u32 = 32;
+
+/// a clipboard, blue while recording and gray with pause bars while paused
+pub fn icon(state: CaptureState) -> Option<Icon> {
+	let paused = matches!(state, CaptureState::Paused { .. });
+	let board = if paused { [100, 116, 139, 255] } else { [59, 130, 246, 255] };
+	let clip = [226, 232, 240, 255];
+	let bars = [30, 41, 59, 255];
+
+	let mut rgba = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+	for y in 0..ICON_SIZE {
+		for x in 0..ICON_SIZE {
+			let pixel = if (11..21).contains(&x) && (2..8).contains(&y) {
+				clip
+			} else if paused && ((10..14).contains(&x) || (18..22).contains(&x)) && (12..26).contains(&y) {
+				bars
+			} else if (5..27).contains(&x) && (4..30).contains(&y) {
+				board
+			} else {
+				[0, 0, 0, 0]
+			};
+			rgba.extend_from_slice(&pixel);
+		}
+	}
+	Icon::from_rgba(rgba, ICON_SIZE, ICON_SIZE).map_err(|err| eprintln!("Failed to draw the tray icon: {err}")).ok()
+}